@@ -0,0 +1,328 @@
+//! A map with a maximum size, see [`BoundedRandMap`
+//! ](../struct.BoundedRandMap.html).
+
+use crate::{DefaultRng, Handle, Iter, RandMap};
+use hashers::null::PassThroughHasher;
+use rand::RngCore;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::BuildHasherDefault;
+
+type KeyMap<T> = HashMap<u64, T, BuildHasherDefault<PassThroughHasher>>;
+
+/// Decides which item a [`BoundedRandMap`](struct.BoundedRandMap.html)
+/// evicts when full. Items are identified by the `u64` of their handles.
+///
+/// The map reports every insertion, access and removal, so the policy
+/// always tracks exactly the items in the map.
+pub trait EvictionPolicy {
+    /// `key` has been inserted.
+    fn insert(&mut self, key: u64);
+
+    /// `key` has been accessed.
+    fn access(&mut self, key: u64);
+
+    /// `key` has been removed by the user.
+    fn remove(&mut self, key: u64);
+
+    /// Chooses the item to evict and forgets it. Only called when there are
+    /// items.
+    fn evict(&mut self) -> Option<u64>;
+
+    /// All items have been removed.
+    fn clear(&mut self);
+}
+
+/// Evicts the least recently used item. Insertion counts as use.
+#[derive(Clone, Debug, Default)]
+pub struct Lru {
+    ticks: KeyMap<u64>,
+    order: BTreeMap<u64, u64>,
+    clock: u64,
+}
+
+impl EvictionPolicy for Lru {
+    fn insert(&mut self, key: u64) {
+        self.clock += 1;
+        if let Some(tick) = self.ticks.insert(key, self.clock) {
+            self.order.remove(&tick);
+        }
+        self.order.insert(self.clock, key);
+    }
+
+    fn access(&mut self, key: u64) {
+        if let Some(tick) = self.ticks.get_mut(&key) {
+            self.order.remove(tick);
+            self.clock += 1;
+            *tick = self.clock;
+            self.order.insert(self.clock, key);
+        }
+    }
+
+    fn remove(&mut self, key: u64) {
+        if let Some(tick) = self.ticks.remove(&key) {
+            self.order.remove(&tick);
+        }
+    }
+
+    fn evict(&mut self) -> Option<u64> {
+        let (&tick, &key) = self.order.iter().next()?;
+        self.order.remove(&tick);
+        self.ticks.remove(&key);
+        Some(key)
+    }
+
+    fn clear(&mut self) {
+        self.ticks.clear();
+        self.order.clear();
+    }
+}
+
+/// Evicts the least frequently used item, or, between items used equally
+/// often, the least recently used one. Insertion counts as the first use.
+#[derive(Clone, Debug, Default)]
+pub struct Lfu {
+    uses: KeyMap<(u64, u64)>,
+    // (uses, tick, key)
+    order: BTreeSet<(u64, u64, u64)>,
+    clock: u64,
+}
+
+impl EvictionPolicy for Lfu {
+    fn insert(&mut self, key: u64) {
+        self.remove(key);
+        self.clock += 1;
+        self.uses.insert(key, (1, self.clock));
+        self.order.insert((1, self.clock, key));
+    }
+
+    fn access(&mut self, key: u64) {
+        if let Some((count, tick)) = self.uses.get_mut(&key) {
+            self.order.remove(&(*count, *tick, key));
+            self.clock += 1;
+            *count += 1;
+            *tick = self.clock;
+            self.order.insert((*count, *tick, key));
+        }
+    }
+
+    fn remove(&mut self, key: u64) {
+        if let Some((count, tick)) = self.uses.remove(&key) {
+            self.order.remove(&(count, tick, key));
+        }
+    }
+
+    fn evict(&mut self) -> Option<u64> {
+        let &(count, tick, key) = self.order.iter().next()?;
+        self.order.remove(&(count, tick, key));
+        self.uses.remove(&key);
+        Some(key)
+    }
+
+    fn clear(&mut self) {
+        self.uses.clear();
+        self.order.clear();
+    }
+}
+
+/// A [`RandMap`](struct.RandMap.html) holding at most a fixed number of
+/// items, e.g. as a cache.
+///
+/// Inserting into a full map evicts an item, chosen by the
+/// [`EvictionPolicy`](trait.EvictionPolicy.html), [`Lru`
+/// ](struct.Lru.html) by default, and returns it. Use [`peek()`
+/// ](#method.peek) to look at an item without it counting as use.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{BoundedRandMap, Lfu};
+///
+/// let mut lru = BoundedRandMap::new(2);
+/// let foo = lru.insert("foo").0;
+/// let bar = lru.insert("bar").0;
+/// assert_eq!(lru.get(foo), Some(&"foo"));
+/// let (baz, evicted) = lru.insert("baz");
+/// assert_eq!(evicted, Some((bar, "bar")));
+/// assert_eq!(lru.len(), 2);
+///
+/// let mut lfu = BoundedRandMap::with_policy(2, Lfu::default());
+/// let foo = lfu.insert("foo").0;
+/// let bar = lfu.insert("bar").0;
+/// lfu.get(bar);
+/// lfu.get(foo);
+/// lfu.get(foo);
+/// assert_eq!(lfu.insert("baz").1, Some((bar, "bar")));
+/// ```
+#[derive(Clone, Debug)]
+pub struct BoundedRandMap<V, P = Lru, R = DefaultRng> {
+    map: RandMap<V, R>,
+    policy: P,
+    capacity: usize,
+}
+
+impl<V> BoundedRandMap<V> {
+    /// Creates an empty map holding at most `capacity` items, evicting the
+    /// least recently used one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    #[inline]
+    pub fn new(capacity: usize) -> Self {
+        Self::with_policy(capacity, Lru::default())
+    }
+}
+
+impl<V, P> BoundedRandMap<V, P> {
+    /// Creates an empty map holding at most `capacity` items, evicting as
+    /// `policy` decides.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    #[inline]
+    pub fn with_policy(capacity: usize, policy: P) -> Self {
+        Self::with_policy_and_rng(capacity, policy, DefaultRng)
+    }
+}
+
+impl<V, P, R> BoundedRandMap<V, P, R> {
+    /// Creates an empty map holding at most `capacity` items, evicting as
+    /// `policy` decides, and drawing handles from `rng`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_policy_and_rng(capacity: usize, policy: P, rng: R) -> Self {
+        assert!(capacity > 0, "capacity must not be zero");
+        Self {
+            map: RandMap::with_rng(rng),
+            policy,
+            capacity,
+        }
+    }
+
+    /// Borrow the contained [`RandMap`](struct.RandMap.html).
+    #[inline]
+    pub fn as_rand_map(&self) -> &RandMap<V, R> {
+        &self.map
+    }
+
+    /// The maximum number of items.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[inline]
+    pub fn contains(&self, handle: Handle<V>) -> bool {
+        self.map.get(handle).is_some()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// The iterator element type is `(Handle<V>, &V)`. Iterating does not
+    /// count as use.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, V> {
+        self.map.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Retrieves a reference to a `V`, without counting as use.
+    #[inline]
+    pub fn peek(&self, handle: Handle<V>) -> Option<&V> {
+        self.map.get(handle)
+    }
+
+    #[inline]
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+}
+
+impl<V, P, R> BoundedRandMap<V, P, R>
+where
+    P: EvictionPolicy,
+{
+    #[inline]
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.policy.clear();
+    }
+
+    /// Retrieves a reference to a `V`, and counts it as used.
+    #[inline]
+    pub fn get(&mut self, handle: Handle<V>) -> Option<&V> {
+        let value = self.map.get(handle)?;
+        self.policy.access(handle.as_u64());
+        Some(value)
+    }
+
+    /// Retrieves a mutable reference to a `V`, and counts it as used.
+    #[inline]
+    pub fn get_mut(&mut self, handle: Handle<V>) -> Option<&mut V> {
+        let value = self.map.get_mut(handle)?;
+        self.policy.access(handle.as_u64());
+        Some(value)
+    }
+
+    /// Remove and return the value with handle `handle`, or `None` if not
+    /// found.
+    #[inline]
+    pub fn remove(&mut self, handle: Handle<V>) -> Option<V> {
+        let value = self.map.remove(handle)?;
+        self.policy.remove(handle.as_u64());
+        Some(value)
+    }
+
+    /// Removes the item the policy would evict next, and returns it.
+    pub fn evict(&mut self) -> Option<(Handle<V>, V)> {
+        if self.map.is_empty() {
+            return None;
+        }
+        let handle = Handle::from_u64(self.policy.evict()?);
+        let value = self
+            .map
+            .remove(handle)
+            .expect("eviction policy out of sync with map");
+        Some((handle, value))
+    }
+}
+
+impl<V, P, R> BoundedRandMap<V, P, R>
+where
+    P: EvictionPolicy,
+    R: RngCore,
+{
+    /// Insert a `V` and get a handle for retrieval, as well as the item
+    /// evicted to make room, if the map was full.
+    pub fn insert(
+        &mut self,
+        value: V,
+    ) -> (Handle<V>, Option<(Handle<V>, V)>) {
+        let evicted = if self.map.len() >= self.capacity {
+            self.evict()
+        } else {
+            None
+        };
+        let handle = self.map.insert(value);
+        self.policy.insert(handle.as_u64());
+        (handle, evicted)
+    }
+}
+
+impl<'a, V, P, R> IntoIterator for &'a BoundedRandMap<V, P, R> {
+    type Item = (Handle<V>, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
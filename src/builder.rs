@@ -0,0 +1,121 @@
+//! Up-front configuration of a map, see [`RandMapBuilder`
+//! ](../struct.RandMapBuilder.html).
+
+use crate::{DefaultRng, Handle, HandleKey, RandMap};
+use std::collections::{HashMap, TryReserveError};
+use std::marker::PhantomData;
+
+/// Configures a [`RandMap`](struct.RandMap.html) before it is created,
+/// obtained from [`RandMap::builder()`](struct.RandMap.html#method.builder).
+///
+/// The options are the initial capacity, the random source `R` and the
+/// handle width `K`. Each defaults to that of [`RandMap::new()`
+/// ](struct.RandMap.html#method.new).
+///
+/// ### Example:
+/// ```
+/// use rand::{rngs::StdRng, SeedableRng};
+/// use rand_map::RandMap;
+///
+/// let mut map = RandMap::builder()
+///     .capacity(1000)
+///     .rng(StdRng::seed_from_u64(4711))
+///     .key::<u32>()
+///     .build();
+/// assert!(map.capacity() >= 1000);
+/// let foo = map.insert("foo");
+/// assert_eq!(std::mem::size_of_val(&foo), 4);
+///
+/// // Fail gracefully rather than abort if the room cannot be had.
+/// let result = RandMap::<[u8; 4096]>::builder()
+///     .capacity(usize::MAX / 2)
+///     .try_build();
+/// assert!(result.is_err());
+/// ```
+#[derive(Clone, Debug)]
+pub struct RandMapBuilder<V, R = DefaultRng, K = u64> {
+    capacity: usize,
+    rng: R,
+    marker: PhantomData<Handle<V, K>>,
+}
+
+impl<V> RandMapBuilder<V> {
+    /// Same as [`RandMap::builder()`
+    /// ](struct.RandMap.html#method.builder).
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            capacity: 0,
+            rng: DefaultRng,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<V, R, K> RandMapBuilder<V, R, K>
+where
+    K: HandleKey,
+{
+    /// Room for at least `capacity` items before the map reallocates.
+    #[inline]
+    pub fn capacity(self, capacity: usize) -> Self {
+        Self { capacity, ..self }
+    }
+
+    /// Handles are to be drawn from `rng`, see [`RandMap::with_rng()`
+    /// ](struct.RandMap.html#method.with_rng).
+    #[inline]
+    pub fn rng<G>(self, rng: G) -> RandMapBuilder<V, G, K> {
+        RandMapBuilder {
+            capacity: self.capacity,
+            rng,
+            marker: PhantomData,
+        }
+    }
+
+    /// Handles are to be of width `L`, see [`HandleKey`
+    /// ](trait.HandleKey.html).
+    #[inline]
+    pub fn key<L>(self) -> RandMapBuilder<V, R, L>
+    where
+        L: HandleKey,
+    {
+        RandMapBuilder {
+            capacity: self.capacity,
+            rng: self.rng,
+            marker: PhantomData,
+        }
+    }
+
+    /// Creates the map.
+    ///
+    /// # Panics
+    ///
+    /// If the capacity overflows `usize`. Allocation failure aborts, use
+    /// [`try_build()`](#method.try_build) to handle it instead.
+    #[inline]
+    pub fn build(self) -> RandMap<V, R, K> {
+        RandMap(
+            HashMap::with_capacity_and_hasher(
+                self.capacity,
+                Default::default(),
+            ),
+            self.rng,
+        )
+    }
+
+    /// Like [`build()`](#method.build), but returns an error rather than
+    /// panicking or aborting if the capacity cannot be had.
+    pub fn try_build(self) -> Result<RandMap<V, R, K>, TryReserveError> {
+        let mut map = RandMap::from_rng(self.rng);
+        map.try_reserve(self.capacity)?;
+        Ok(map)
+    }
+}
+
+impl<V> Default for RandMapBuilder<V> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,207 @@
+//! A map for sharing between threads, see [`ConcurrentRandMap`
+//! ](../struct.ConcurrentRandMap.html).
+
+use crate::{Handle, RandMap};
+use rand::Rng;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+const DEFAULT_SHARD_BITS: u32 = 4;
+
+/// A [`RandMap`](struct.RandMap.html) that may be shared between threads
+/// without an outer lock.
+///
+/// The map is split into shards, each behind its own `RwLock`. An item goes
+/// to the shard given by the high bits of its handle, and as handles are
+/// uniformly random the shards are evenly loaded.
+///
+/// Items are accessed through closures, so that no lock is held longer than
+/// necessary. A panic in such a closure does not make the map unusable, but
+/// the item may be left partially updated.
+///
+/// ### Example:
+/// ```
+/// use rand_map::ConcurrentRandMap;
+/// use std::{sync::Arc, thread};
+///
+/// let map = Arc::new(ConcurrentRandMap::new());
+/// let handles: Vec<_> = (0..4)
+///     .map(|i| {
+///         let map = Arc::clone(&map);
+///         thread::spawn(move || map.insert(i)).join().unwrap()
+///     })
+///     .collect();
+/// assert_eq!(map.len(), 4);
+/// assert_eq!(map.update(handles[1], |v| {
+///     *v += 10;
+///     *v
+/// }), Some(11));
+/// assert_eq!(map.get_with(handles[1], |v| *v), Some(11));
+/// assert_eq!(map.remove(handles[0]), Some(0));
+/// assert_eq!(map.get(handles[0]), None);
+/// ```
+#[derive(Debug)]
+pub struct ConcurrentRandMap<V> {
+    shards: Box<[RwLock<RandMap<V>>]>,
+    shard_bits: u32,
+}
+
+impl<V> ConcurrentRandMap<V> {
+    /// Creates an empty map with 16 shards.
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_shard_bits(DEFAULT_SHARD_BITS)
+    }
+
+    /// Creates an empty map with `1 << shard_bits` shards.
+    ///
+    /// # Panics
+    ///
+    /// If `shard_bits` is greater than 16.
+    pub fn with_shard_bits(shard_bits: u32) -> Self {
+        assert!(shard_bits <= 16, "too many shards");
+        Self {
+            shards: (0..1 << shard_bits)
+                .map(|_| RwLock::new(RandMap::new()))
+                .collect(),
+            shard_bits,
+        }
+    }
+
+    /// Clears the map, one shard at a time.
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            write(shard).clear();
+        }
+    }
+
+    /// Whether `handle` refers to an item in the map.
+    #[inline]
+    pub fn contains(&self, handle: Handle<V>) -> bool {
+        self.read(handle).as_hash_map().contains_key(&handle)
+    }
+
+    /// Calls `f` with a reference to the `V` of `handle`, and returns its
+    /// result, or `None` if not found. The shard of `handle` is read locked
+    /// during the call.
+    #[inline]
+    pub fn get_with<F, T>(&self, handle: Handle<V>, f: F) -> Option<T>
+    where
+        F: FnOnce(&V) -> T,
+    {
+        self.read(handle).get(handle).map(f)
+    }
+
+    /// Insert a `V` and get a handle for retrieval. Like [`RandMap::insert()`
+    /// ](struct.RandMap.html#method.insert), no existing item is ever
+    /// overwritten.
+    pub fn insert(&self, mut value: V) -> Handle<V> {
+        let mut rng = rand::thread_rng();
+        loop {
+            let handle = rng.gen();
+            match self.write(handle).try_insert_key_value(handle, value) {
+                Ok(()) => return handle,
+                Err(v) => value = v,
+            }
+        }
+    }
+
+    /// Insert a key-value pair and return the value previously stored under
+    /// `key`, if any.
+    #[inline]
+    pub fn insert_key_value(&self, key: Handle<V>, value: V) -> Option<V> {
+        self.write(key).replace_key_value(key, value)
+    }
+
+    /// Moves all items into a [`RandMap`](struct.RandMap.html).
+    pub fn into_rand_map(self) -> RandMap<V> {
+        let mut map = RandMap::new();
+        for shard in self.shards.into_vec() {
+            let shard = shard.into_inner().unwrap_or_else(|e| e.into_inner());
+            map.extend(shard);
+        }
+        map
+    }
+
+    /// Whether the map is empty. Under concurrent modification this is but
+    /// a snapshot, see [`len()`](#method.len).
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| read(shard).is_empty())
+    }
+
+    /// The number of items. The shards are counted one at a time, so under
+    /// concurrent modification the result is approximate.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| read(shard).len()).sum()
+    }
+
+    /// Remove and return the value with handle `handle`, or `None` if not
+    /// found.
+    #[inline]
+    pub fn remove(&self, handle: Handle<V>) -> Option<V> {
+        self.write(handle).remove(handle)
+    }
+
+    /// Calls `f` with a mutable reference to the `V` of `handle`, and
+    /// returns its result, or `None` if not found. The shard of `handle` is
+    /// write locked during the call.
+    #[inline]
+    pub fn update<F, T>(&self, handle: Handle<V>, f: F) -> Option<T>
+    where
+        F: FnOnce(&mut V) -> T,
+    {
+        self.write(handle).get_mut(handle).map(f)
+    }
+
+    #[inline]
+    fn read(&self, handle: Handle<V>) -> RwLockReadGuard<'_, RandMap<V>> {
+        read(&self.shards[self.shard_index(handle)])
+    }
+
+    #[inline]
+    fn shard_index(&self, handle: Handle<V>) -> usize {
+        handle
+            .as_u64()
+            .checked_shr(64 - self.shard_bits)
+            .unwrap_or(0) as usize
+    }
+
+    #[inline]
+    fn write(&self, handle: Handle<V>) -> RwLockWriteGuard<'_, RandMap<V>> {
+        write(&self.shards[self.shard_index(handle)])
+    }
+}
+
+impl<V> ConcurrentRandMap<V>
+where
+    V: Clone,
+{
+    /// Retrieves a clone of the `V` of `handle`.
+    #[inline]
+    pub fn get(&self, handle: Handle<V>) -> Option<V> {
+        self.get_with(handle, V::clone)
+    }
+}
+
+impl<V> Default for ConcurrentRandMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> From<RandMap<V>> for ConcurrentRandMap<V> {
+    fn from(map: RandMap<V>) -> Self {
+        let concurrent = Self::new();
+        for (handle, value) in map {
+            concurrent.insert_key_value(handle, value);
+        }
+        concurrent
+    }
+}
+
+fn read<V>(shard: &RwLock<RandMap<V>>) -> RwLockReadGuard<'_, RandMap<V>> {
+    shard.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write<V>(shard: &RwLock<RandMap<V>>) -> RwLockWriteGuard<'_, RandMap<V>> {
+    shard.write().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,558 @@
+//! A map persisted by a write-ahead log, see [`DurableRandMap`
+//! ](../struct.DurableRandMap.html).
+
+use crate::snapshot::{crc32, write_atomically, Reader};
+use crate::{
+    DefaultRng, Handle, Iter, RandMap, SnapshotError, SnapshotValue,
+};
+use rand::RngCore;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+
+const LOG_MAGIC: &[u8; 8] = b"RANDWAL\0";
+const LOG_VERSION: u32 = 2;
+const LOG_HEADER_LEN: usize = 8 + 4;
+// Payload length and checksum, and the checksum of those two.
+const RECORD_HEADER_LEN: usize = 4 + 4 + 4;
+
+const PUT: u8 = 1;
+const REMOVE: u8 = 2;
+const CLEAR: u8 = 3;
+
+const SNAPSHOT_FILE: &str = "snapshot";
+const LOG_FILE: &str = "log";
+
+/// A [`RandMap`](struct.RandMap.html) that records every change in a log
+/// file, so that its contents, handles included, survive a restart or a
+/// crash.
+///
+/// The map lives in a directory holding a snapshot, written by
+/// [`RandMap::save_to()`](struct.RandMap.html#method.save_to), and a log of
+/// the changes since. [`open()`](#method.open) loads the snapshot and replays
+/// the log. A record torn by a crash at the end of the log is discarded;
+/// damage anywhere else is an error. [`compact()`](#method.compact) folds
+/// the log into a fresh snapshot.
+///
+/// Each change is written to the log before it takes effect. By default,
+/// the log is also synced to disk on every change, which is slow, but
+/// ensures that a change is durable once the method returns. See
+/// [`set_sync()`](#method.set_sync). Should a write fail, the log is cut
+/// back to where it was, so that no partial record precedes later ones. If
+/// even that fails, the map refuses further changes until [`compact()`
+/// ](#method.compact) succeeds.
+///
+/// ### Example:
+/// ```
+/// use rand_map::DurableRandMap;
+///
+/// let dir = std::env::temp_dir().join("rand_map_doc_durable");
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// let mut map = DurableRandMap::open(&dir).unwrap();
+/// let foo = map.insert("foo".to_string()).unwrap();
+/// let bar = map.insert("bar".to_string()).unwrap();
+/// map.get_mut(bar).unwrap().push('!');
+/// assert_eq!(map.remove(foo).unwrap(), Some("foo".to_string()));
+/// drop(map);
+///
+/// let mut map = DurableRandMap::<String>::open(&dir).unwrap();
+/// assert_eq!(map.get(foo), None);
+/// assert_eq!(map.get(bar).unwrap(), "bar!");
+/// map.compact().unwrap();
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub struct DurableRandMap<V, R = DefaultRng>
+where
+    V: SnapshotValue,
+{
+    map: RandMap<V, R>,
+    dir: PathBuf,
+    log: File,
+    // The length of the log after the last complete append.
+    log_len: u64,
+    // Set if a failed append could not be undone.
+    log_broken: bool,
+    sync: bool,
+    // Items handed out by `get_mut()` and not yet logged.
+    pending: Vec<Handle<V>>,
+}
+
+impl<V> DurableRandMap<V>
+where
+    V: SnapshotValue,
+{
+    /// Opens the map in directory `dir`, creating both if need be.
+    #[inline]
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, WalError> {
+        Self::open_with_rng(dir, DefaultRng)
+    }
+}
+
+impl<V, R> DurableRandMap<V, R>
+where
+    V: SnapshotValue,
+{
+    /// Opens the map in directory `dir`, creating both if need be, and
+    /// draws new handles from `rng`.
+    pub fn open_with_rng<P: AsRef<Path>>(
+        dir: P,
+        rng: R,
+    ) -> Result<Self, WalError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let mut map = RandMap::with_rng(rng);
+        match RandMap::<V>::load_from(dir.join(SNAPSHOT_FILE)) {
+            Err(SnapshotError::Io(ref e))
+                if e.kind() == io::ErrorKind::NotFound => {}
+            snapshot => map.extend(snapshot?),
+        }
+
+        let log_path = dir.join(LOG_FILE);
+        let bytes = match fs::read(&log_path) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            bytes => bytes?,
+        };
+        let valid = if bytes.len() < LOG_HEADER_LEN {
+            if !LOG_MAGIC.starts_with(&bytes[..bytes.len().min(8)]) {
+                return Err(WalError::Corrupt { offset: 0 });
+            }
+            // Missing, or torn before its header was complete.
+            write_atomically(&log_path, &log_header())?;
+            LOG_HEADER_LEN
+        } else {
+            check_log_header(&bytes)?;
+            replay(&bytes, &mut map)?
+        };
+        let log = OpenOptions::new().append(true).open(&log_path)?;
+        if valid < bytes.len() {
+            log.set_len(valid as u64)?;
+            log.sync_all()?;
+        }
+        Ok(Self {
+            map,
+            dir,
+            log,
+            log_len: valid as u64,
+            log_broken: false,
+            sync: true,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Borrow the contained [`RandMap`](struct.RandMap.html).
+    #[inline]
+    pub fn as_rand_map(&self) -> &RandMap<V, R> {
+        &self.map
+    }
+
+    /// Removes all items.
+    pub fn clear(&mut self) -> Result<(), WalError> {
+        self.append(&[CLEAR])?;
+        self.pending.clear();
+        self.map.clear();
+        Ok(())
+    }
+
+    /// Writes the contents to a fresh snapshot, and empties the log.
+    ///
+    /// The snapshot replaces the old one before the log is emptied. Should
+    /// a crash intervene, the old log is replayed onto the new snapshot,
+    /// which gives the same contents.
+    pub fn compact(&mut self) -> Result<(), WalError> {
+        self.flush_pending()?;
+        self.map.save_to(self.dir.join(SNAPSHOT_FILE))?;
+        let log_path = self.dir.join(LOG_FILE);
+        write_atomically(&log_path, &log_header())?;
+        self.log = OpenOptions::new().append(true).open(&log_path)?;
+        self.log_len = LOG_HEADER_LEN as u64;
+        self.log_broken = false;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn contains(&self, handle: Handle<V>) -> bool {
+        self.map.get(handle).is_some()
+    }
+
+    /// The directory the map lives in.
+    #[inline]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    #[inline]
+    pub fn get(&self, handle: Handle<V>) -> Option<&V> {
+        self.map.get(handle)
+    }
+
+    /// Retrieves a mutable reference to a `V`. The changed value is logged
+    /// by [`DurableRefMut::commit()`
+    /// ](struct.DurableRefMut.html#method.commit), or else by the next
+    /// change to the map.
+    pub fn get_mut(
+        &mut self,
+        handle: Handle<V>,
+    ) -> Option<DurableRefMut<'_, V, R>> {
+        self.map.get(handle)?;
+        // Should this fail, it is retried by the next change.
+        let _ = self.flush_pending();
+        if !self.pending.contains(&handle) {
+            self.pending.push(handle);
+        }
+        Some(DurableRefMut { map: self, handle })
+    }
+
+    /// Insert a key-value pair. Any existing item with handle `key` is
+    /// replaced.
+    pub fn insert_key_value(
+        &mut self,
+        key: Handle<V>,
+        value: V,
+    ) -> Result<(), WalError> {
+        self.flush_pending()?;
+        self.append(&put_record(key, &value))?;
+        self.map.insert_key_value(key, value);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// The iterator element type is `(Handle<V>, &V)`.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, V> {
+        self.map.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Remove and return the value with handle `handle`, or `None` if not
+    /// found.
+    pub fn remove(
+        &mut self,
+        handle: Handle<V>,
+    ) -> Result<Option<V>, WalError> {
+        self.flush_pending()?;
+        if self.map.get(handle).is_none() {
+            return Ok(None);
+        }
+        let mut record = vec![REMOVE];
+        record.extend_from_slice(&handle.as_u64().to_le_bytes());
+        self.append(&record)?;
+        Ok(self.map.remove(handle))
+    }
+
+    /// Whether each change is synced to disk before returning. If not, a
+    /// change survives a crash of the process, but not necessarily one of
+    /// the system, unless followed by [`sync()`](#method.sync).
+    #[inline]
+    pub fn set_sync(&mut self, sync: bool) {
+        self.sync = sync;
+    }
+
+    /// Syncs all changes to disk.
+    pub fn sync(&mut self) -> Result<(), WalError> {
+        self.flush_pending()?;
+        self.log.sync_data()?;
+        Ok(())
+    }
+
+    /// Calls `f` with a mutable reference to the `V` of `handle`, logs the
+    /// changed value, and returns the result of `f`, or `None` if not found.
+    ///
+    /// Should logging fail, the change has been made in memory only.
+    pub fn update<F, T>(
+        &mut self,
+        handle: Handle<V>,
+        f: F,
+    ) -> Result<Option<T>, WalError>
+    where
+        F: FnOnce(&mut V) -> T,
+    {
+        self.flush_pending()?;
+        let result = match self.map.get_mut(handle) {
+            Some(value) => f(value),
+            None => return Ok(None),
+        };
+        self.pending.push(handle);
+        self.flush_pending()?;
+        Ok(Some(result))
+    }
+
+    // Appends a record, or leaves the log as it was. Should that be
+    // impossible, e.g. as the disk is gone, refuses any further appends, as
+    // they would follow a partial record.
+    fn append(&mut self, payload: &[u8]) -> io::Result<()> {
+        if self.log_broken {
+            return Err(io::Error::other(
+                "log holds a partial record from a failed write",
+            ));
+        }
+        let mut record =
+            Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(&crc32(payload).to_le_bytes());
+        record.extend_from_slice(&crc32(&record).to_le_bytes());
+        record.extend_from_slice(payload);
+        let result = self.log.write_all(&record).and_then(|()| {
+            if self.sync {
+                self.log.sync_data()?;
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) => self.log_len += record.len() as u64,
+            Err(_) => {
+                if self.log.set_len(self.log_len).is_err() {
+                    self.log_broken = true;
+                }
+            }
+        }
+        result
+    }
+
+    fn flush_pending(&mut self) -> io::Result<()> {
+        while let Some(&handle) = self.pending.last() {
+            if let Some(value) = self.map.get(handle) {
+                let record = put_record(handle, value);
+                self.append(&record)?;
+            }
+            self.pending.pop();
+        }
+        Ok(())
+    }
+}
+
+impl<V, R> DurableRandMap<V, R>
+where
+    V: SnapshotValue,
+    R: RngCore,
+{
+    /// Insert a `V` and get a handle for retrieval.
+    pub fn insert(&mut self, value: V) -> Result<Handle<V>, WalError> {
+        self.flush_pending()?;
+        let handle = self.map.insert(value);
+        let record = put_record(handle, self.map.get(handle).unwrap());
+        if let Err(e) = self.append(&record) {
+            self.map.remove(handle);
+            return Err(e.into());
+        }
+        Ok(handle)
+    }
+}
+
+/// Logs a change made by [`get_mut()`](#method.get_mut) that has not been
+/// committed, ignoring any error.
+impl<V, R> Drop for DurableRandMap<V, R>
+where
+    V: SnapshotValue,
+{
+    fn drop(&mut self) {
+        let _ = self.flush_pending();
+    }
+}
+
+impl<V, R> fmt::Debug for DurableRandMap<V, R>
+where
+    V: SnapshotValue + fmt::Debug,
+    R: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DurableRandMap")
+            .field("map", &self.map)
+            .field("dir", &self.dir)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A mutable reference to an item of a [`DurableRandMap`
+/// ](struct.DurableRandMap.html), see [`DurableRandMap::get_mut()`
+/// ](struct.DurableRandMap.html#method.get_mut).
+pub struct DurableRefMut<'a, V, R = DefaultRng>
+where
+    V: SnapshotValue,
+{
+    map: &'a mut DurableRandMap<V, R>,
+    handle: Handle<V>,
+}
+
+impl<'a, V, R> DurableRefMut<'a, V, R>
+where
+    V: SnapshotValue,
+{
+    /// Logs the changed value.
+    #[inline]
+    pub fn commit(self) -> Result<(), WalError> {
+        self.map.flush_pending()?;
+        Ok(())
+    }
+}
+
+impl<'a, V, R> Deref for DurableRefMut<'a, V, R>
+where
+    V: SnapshotValue,
+{
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        self.map.map.get(self.handle).unwrap()
+    }
+}
+
+impl<'a, V, R> DerefMut for DurableRefMut<'a, V, R>
+where
+    V: SnapshotValue,
+{
+    fn deref_mut(&mut self) -> &mut V {
+        self.map.map.get_mut(self.handle).unwrap()
+    }
+}
+
+/// The error returned when the log of a [`DurableRandMap`
+/// ](struct.DurableRandMap.html) cannot be read or written.
+#[derive(Debug)]
+pub enum WalError {
+    /// Reading or writing the log failed.
+    Io(io::Error),
+    /// The snapshot could not be loaded or saved.
+    Snapshot(SnapshotError),
+    /// The log is damaged at byte offset `offset`, other than by a torn
+    /// final record, or was written by a newer version of this crate.
+    Corrupt { offset: u64 },
+}
+
+impl fmt::Display for WalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalError::Io(e) => write!(f, "log I/O error: {}", e),
+            WalError::Snapshot(e) => e.fmt(f),
+            WalError::Corrupt { offset } => {
+                write!(f, "log corrupt at offset {}", offset)
+            }
+        }
+    }
+}
+
+impl Error for WalError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WalError::Io(e) => Some(e),
+            WalError::Snapshot(e) => Some(e),
+            WalError::Corrupt { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for WalError {
+    fn from(e: io::Error) -> Self {
+        WalError::Io(e)
+    }
+}
+
+impl From<SnapshotError> for WalError {
+    fn from(e: SnapshotError) -> Self {
+        WalError::Snapshot(e)
+    }
+}
+
+fn log_header() -> Vec<u8> {
+    let mut header = LOG_MAGIC.to_vec();
+    header.extend_from_slice(&LOG_VERSION.to_le_bytes());
+    header
+}
+
+fn check_log_header(bytes: &[u8]) -> Result<(), WalError> {
+    if bytes[..LOG_HEADER_LEN] == log_header()[..] {
+        Ok(())
+    } else {
+        Err(WalError::Corrupt { offset: 0 })
+    }
+}
+
+fn put_record<V: SnapshotValue>(handle: Handle<V>, value: &V) -> Vec<u8> {
+    let mut record = vec![PUT];
+    record.extend_from_slice(&handle.as_u64().to_le_bytes());
+    value.encode(&mut record);
+    record
+}
+
+// Applies the records of `log` to `map`, and returns the length of the valid
+// part of the log. Only the last record may be torn, i.e. its header or its
+// payload run past the end of the log, or fail their checksum with nothing
+// but zeros after them, as a crash may leave zero-filled blocks at the end of
+// the file. As the header is checked before its length is trusted, a damaged
+// length is not mistaken for a torn record.
+fn replay<V, R>(
+    log: &[u8],
+    map: &mut RandMap<V, R>,
+) -> Result<usize, WalError>
+where
+    V: SnapshotValue,
+{
+    let mut offset = LOG_HEADER_LEN;
+    while offset < log.len() {
+        let corrupt = WalError::Corrupt {
+            offset: offset as u64,
+        };
+        let mut reader = Reader(&log[offset..]);
+        let (len, checksum, header_checksum) =
+            match (reader.u32(), reader.u32(), reader.u32()) {
+                (Some(len), Some(checksum), Some(header_checksum)) => {
+                    (len, checksum, header_checksum)
+                }
+                _ => break,
+            };
+        if crc32(&log[offset..offset + 8]) != header_checksum {
+            if zeros(&log[offset + RECORD_HEADER_LEN..]) {
+                break;
+            }
+            return Err(corrupt);
+        }
+        let payload = match reader.take(len as usize) {
+            Some(payload) => payload,
+            None => break,
+        };
+        let end = offset + RECORD_HEADER_LEN + payload.len();
+        if crc32(payload) != checksum {
+            if zeros(&log[end..]) {
+                break;
+            }
+            return Err(corrupt);
+        }
+        apply(payload, map).ok_or(corrupt)?;
+        offset = end;
+    }
+    Ok(offset)
+}
+
+#[inline]
+fn zeros(bytes: &[u8]) -> bool {
+    bytes.iter().all(|&byte| byte == 0)
+}
+
+fn apply<V, R>(payload: &[u8], map: &mut RandMap<V, R>) -> Option<()>
+where
+    V: SnapshotValue,
+{
+    let mut reader = Reader(payload);
+    match reader.take(1)?[0] {
+        PUT => {
+            let handle = Handle::from_u64(reader.u64()?);
+            map.insert_key_value(handle, V::decode(reader.0)?);
+        }
+        REMOVE => {
+            map.remove(Handle::from_u64(reader.u64()?));
+        }
+        CLEAR => map.clear(),
+        _ => return None,
+    }
+    Some(())
+}
@@ -0,0 +1,176 @@
+//! String encodings of [`Handle`](../struct.Handle.html)s.
+
+use crate::{Handle, HandleKey};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+const CROCKFORD32: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const BASE62: &[u8; 62] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// A compact, URL-safe string encoding of a [`Handle`](struct.Handle.html).
+///
+/// All encodings omit leading zeros, i.e. the handle `0` is encoded as `"0"`.
+/// The maximum lengths given are for `u64` handles, `u128` handles need about
+/// twice as many characters.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{Handle, HandleEncoding};
+///
+/// let handle = Handle::<()>::from_u64(4711);
+/// assert_eq!(handle.to_string(), "4K7");
+/// assert_eq!("4k7".parse(), Ok(handle));
+/// assert_eq!("4-K7".parse(), Ok(handle));
+/// assert_eq!(handle.encode(HandleEncoding::Base62), "1Dz");
+/// assert_eq!(Handle::decode("1Dz", HandleEncoding::Base62), Ok(handle));
+/// assert_eq!(handle.encode(HandleEncoding::Hex), "1267");
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum HandleEncoding {
+    /// [Crockford's base32](https://www.crockford.com/base32.html), at most
+    /// 13 characters. Decoding is case insensitive, reads `I` and `L` as `1`
+    /// and `O` as `0`, and ignores hyphens. This is the encoding of
+    /// `Display` and `FromStr`.
+    #[default]
+    Crockford32,
+    /// Digits, upper case and lower case letters, at most 11 characters.
+    /// Case sensitive.
+    Base62,
+    /// Lower case hexadecimal, at most 16 characters. Decoding is case
+    /// insensitive.
+    Hex,
+}
+
+impl HandleEncoding {
+    fn radix(self) -> u128 {
+        match self {
+            HandleEncoding::Crockford32 => 32,
+            HandleEncoding::Base62 => 62,
+            HandleEncoding::Hex => 16,
+        }
+    }
+
+    fn digit(self, value: u128) -> char {
+        let value = value as usize;
+        match self {
+            HandleEncoding::Crockford32 => CROCKFORD32[value] as char,
+            HandleEncoding::Base62 => BASE62[value] as char,
+            HandleEncoding::Hex => {
+                std::char::from_digit(value as u32, 16).unwrap()
+            }
+        }
+    }
+
+    fn value(self, digit: char) -> Option<u128> {
+        let value = match self {
+            HandleEncoding::Crockford32 => match digit.to_ascii_uppercase() {
+                'I' | 'L' => 1,
+                'O' => 0,
+                d => CROCKFORD32.iter().position(|&c| c as char == d)?,
+            },
+            HandleEncoding::Base62 => {
+                BASE62.iter().position(|&c| c as char == digit)?
+            }
+            HandleEncoding::Hex => digit.to_digit(16)? as usize,
+        };
+        Some(value as u128)
+    }
+
+    fn ignores(self, c: char) -> bool {
+        self == HandleEncoding::Crockford32 && c == '-'
+    }
+
+    pub(crate) fn encode<K: HandleKey>(self, key: K) -> String {
+        let mut u = key.to_u128();
+        let radix = self.radix();
+        let mut digits = Vec::new();
+        loop {
+            digits.push(self.digit(u % radix));
+            u /= radix;
+            if u == 0 {
+                break;
+            }
+        }
+        digits.iter().rev().collect()
+    }
+
+    pub(crate) fn decode<K: HandleKey>(
+        self,
+        s: &str,
+    ) -> Result<K, ParseHandleError> {
+        let mut u: u128 = 0;
+        let mut empty = true;
+        for (index, c) in s.char_indices() {
+            if self.ignores(c) {
+                continue;
+            }
+            let value = self
+                .value(c)
+                .ok_or(ParseHandleError::InvalidDigit { index, digit: c })?;
+            u = u
+                .checked_mul(self.radix())
+                .and_then(|u| u.checked_add(value))
+                .ok_or(ParseHandleError::Overflow)?;
+            empty = false;
+        }
+        if empty {
+            Err(ParseHandleError::Empty)
+        } else {
+            K::from_u128(u).ok_or(ParseHandleError::Overflow)
+        }
+    }
+}
+
+/// The error returned when a string cannot be decoded as a [`Handle`
+/// ](struct.Handle.html).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ParseHandleError {
+    /// There are no digits.
+    Empty,
+    /// `digit`, at byte offset `index`, is not valid in the encoding.
+    InvalidDigit { index: usize, digit: char },
+    /// The number is too big for a handle.
+    Overflow,
+}
+
+impl fmt::Display for ParseHandleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseHandleError::Empty => f.write_str("empty handle"),
+            ParseHandleError::InvalidDigit { index, digit } => write!(
+                f,
+                "invalid digit {:?} at index {} in handle",
+                digit, index
+            ),
+            ParseHandleError::Overflow => f.write_str("handle too big"),
+        }
+    }
+}
+
+impl Error for ParseHandleError {}
+
+/// Uses the default [`HandleEncoding`](enum.HandleEncoding.html), i.e.
+/// Crockford's base32.
+impl<V, K> fmt::Display for Handle<V, K>
+where
+    K: HandleKey,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.encode(HandleEncoding::default()))
+    }
+}
+
+/// Uses the default [`HandleEncoding`](enum.HandleEncoding.html), i.e.
+/// Crockford's base32.
+impl<V, K> FromStr for Handle<V, K>
+where
+    K: HandleKey,
+{
+    type Err = ParseHandleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::decode(s, HandleEncoding::default())
+    }
+}
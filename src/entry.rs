@@ -0,0 +1,215 @@
+//! The entry API of [`RandMap`](../struct.RandMap.html).
+
+use crate::{Handle, HandleKey};
+use std::collections::hash_map;
+
+/// A view into a single entry of a [`RandMap`](struct.RandMap.html), which
+/// may either be vacant or occupied. Returned by [`RandMap::entry()`
+/// ](struct.RandMap.html#method.entry).
+///
+/// Mirrors [`std::collections::hash_map::Entry`
+/// ](https://doc.rust-lang.org/std/collections/hash_map/enum.Entry.html),
+/// except that handles are passed by value.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{Entry, Handle, RandMap};
+///
+/// let mut map = RandMap::new();
+/// let key = Handle::from_u64(4711);
+/// *map.entry(key).or_insert(0) += 1;
+/// map.entry(key).and_modify(|v| *v += 10).or_insert(0);
+/// assert_eq!(map.get(key), Some(&11));
+/// match map.entry(key) {
+///     Entry::Occupied(entry) => assert_eq!(entry.remove(), 11),
+///     Entry::Vacant(_) => unreachable!(),
+/// }
+/// match map.entry(key) {
+///     Entry::Occupied(_) => unreachable!(),
+///     Entry::Vacant(entry) => assert_eq!(*entry.insert(17), 17),
+/// }
+/// assert_eq!(*map.entry(key).or_insert_with(|| 0), 17);
+/// ```
+pub enum Entry<'a, V, K = u64> {
+    Occupied(OccupiedEntry<'a, V, K>),
+    Vacant(VacantEntry<'a, V, K>),
+}
+
+impl<'a, V, K> Entry<'a, V, K>
+where
+    K: HandleKey,
+{
+    pub(crate) fn new(entry: hash_map::Entry<'a, Handle<V, K>, V>) -> Self {
+        match entry {
+            hash_map::Entry::Occupied(entry) => {
+                Entry::Occupied(OccupiedEntry(entry))
+            }
+            hash_map::Entry::Vacant(entry) => {
+                Entry::Vacant(VacantEntry(entry))
+            }
+        }
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any
+    /// potential inserts into the map.
+    #[inline]
+    pub fn and_modify<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+
+    /// The handle of this entry.
+    #[inline]
+    pub fn key(&self) -> Handle<V, K> {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting `default` if empty, and
+    /// returns a mutable reference to the value in the entry.
+    #[inline]
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of `default`
+    /// if empty, and returns a mutable reference to the value in the entry.
+    #[inline]
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Like [`or_insert_with()`](#method.or_insert_with), but `default` gets
+    /// the handle of the entry.
+    #[inline]
+    pub fn or_insert_with_key<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce(Handle<V, K>) -> V,
+    {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+}
+
+impl<'a, V, K> Entry<'a, V, K>
+where
+    V: Default,
+    K: HandleKey,
+{
+    /// Ensures a value is in the entry by inserting `V::default()` if empty,
+    /// and returns a mutable reference to the value in the entry.
+    #[inline]
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(V::default)
+    }
+}
+
+/// A view into an occupied entry in a [`RandMap`](struct.RandMap.html). It
+/// is part of the [`Entry`](enum.Entry.html) enum.
+pub struct OccupiedEntry<'a, V, K = u64>(
+    hash_map::OccupiedEntry<'a, Handle<V, K>, V>,
+);
+
+impl<'a, V, K> OccupiedEntry<'a, V, K>
+where
+    K: HandleKey,
+{
+    /// Gets a reference to the value in the entry.
+    #[inline]
+    pub fn get(&self) -> &V {
+        self.0.get()
+    }
+
+    /// Gets a mutable reference to the value in the entry, bound to the
+    /// entry. See [`into_mut()`](#method.into_mut) for a reference bound to
+    /// the map.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut V {
+        self.0.get_mut()
+    }
+
+    /// Sets the value of the entry and returns the old value.
+    #[inline]
+    pub fn insert(&mut self, value: V) -> V {
+        self.0.insert(value)
+    }
+
+    /// Converts the entry into a mutable reference to its value, bound to
+    /// the map.
+    #[inline]
+    pub fn into_mut(self) -> &'a mut V {
+        self.0.into_mut()
+    }
+
+    /// The handle of this entry.
+    #[inline]
+    pub fn key(&self) -> Handle<V, K> {
+        *self.0.key()
+    }
+
+    /// Takes the value out of the map.
+    #[inline]
+    pub fn remove(self) -> V {
+        self.0.remove()
+    }
+
+    /// Takes the handle and the value out of the map.
+    #[inline]
+    pub fn remove_entry(self) -> (Handle<V, K>, V) {
+        self.0.remove_entry()
+    }
+}
+
+/// A view into a vacant entry in a [`RandMap`](struct.RandMap.html). It is
+/// part of the [`Entry`](enum.Entry.html) enum.
+pub struct VacantEntry<'a, V, K = u64>(
+    hash_map::VacantEntry<'a, Handle<V, K>, V>,
+);
+
+impl<'a, V, K> VacantEntry<'a, V, K>
+where
+    K: HandleKey,
+{
+    /// Sets the value of the entry and returns a mutable reference to it.
+    #[inline]
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.0.insert(value)
+    }
+
+    /// Take ownership of the handle.
+    #[inline]
+    pub fn into_key(self) -> Handle<V, K> {
+        self.0.into_key()
+    }
+
+    /// The handle that would be used when inserting a value through this
+    /// entry.
+    #[inline]
+    pub fn key(&self) -> Handle<V, K> {
+        *self.0.key()
+    }
+}
@@ -0,0 +1,349 @@
+//! A map whose items expire, see [`ExpiringRandMap`
+//! ](../struct.ExpiringRandMap.html).
+
+use crate::{DefaultRng, Handle, Iter, RandMap};
+use hashers::null::PassThroughHasher;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A source of the current time for an [`ExpiringRandMap`
+/// ](struct.ExpiringRandMap.html).
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// The system's monotonic clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to, for testing expiry without
+/// sleeping. Clones share their time, so a clone kept outside a map
+/// controls the time inside.
+#[derive(Clone, Debug)]
+pub struct ManualClock(Arc<Mutex<Instant>>);
+
+impl ManualClock {
+    /// Creates a clock standing at the current time.
+    #[inline]
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(Instant::now())))
+    }
+
+    /// Moves the clock, and all its clones, forward by `duration`.
+    #[inline]
+    pub fn advance(&self, duration: Duration) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [`RandMap`](struct.RandMap.html) whose items may be given a time to
+/// live, e.g. for sessions or nonces.
+///
+/// Expired items are treated as absent by all lookups, and removed by
+/// [`get_mut()`](#method.get_mut) and [`remove()`](#method.remove) once
+/// found. Otherwise they linger, and count towards [`len()`
+/// ](#method.len), until [`purge_expired()`](#method.purge_expired) is
+/// called.
+///
+/// An item expires when the [`Clock`](trait.Clock.html) reaches its
+/// deadline.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{ExpiringRandMap, ManualClock};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let mut map = ExpiringRandMap::with_clock(clock.clone());
+/// let foo = map.insert_with_ttl("foo", Duration::from_secs(10));
+/// let bar = map.insert_with_ttl("bar", Duration::from_secs(20));
+/// let baz = map.insert("baz");
+/// assert_eq!(map.ttl(foo), Some(Duration::from_secs(10)));
+///
+/// clock.advance(Duration::from_secs(10));
+/// assert_eq!(map.get(foo), None);
+/// assert_eq!(map.get(bar), Some(&"bar"));
+/// assert_eq!(map.len(), 3);
+/// assert_eq!(map.purge_expired(), vec![(foo, "foo")]);
+/// assert_eq!(map.len(), 2);
+///
+/// clock.advance(Duration::from_secs(3600));
+/// assert_eq!(map.get_mut(bar), None);
+/// assert_eq!(map.len(), 1);
+/// assert_eq!(map.get(baz), Some(&"baz"));
+/// assert_eq!(map.ttl(baz), None);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExpiringRandMap<V, C = SystemClock, R = DefaultRng> {
+    map: RandMap<V, R>,
+    deadlines: Deadlines<V>,
+    clock: C,
+}
+
+type Deadlines<V> =
+    HashMap<Handle<V>, Instant, BuildHasherDefault<PassThroughHasher>>;
+
+impl<V> ExpiringRandMap<V> {
+    /// Creates an empty map using the system clock.
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<V, C> ExpiringRandMap<V, C> {
+    /// Creates an empty map using `clock`.
+    #[inline]
+    pub fn with_clock(clock: C) -> Self {
+        Self::with_clock_and_rng(clock, DefaultRng)
+    }
+}
+
+impl<V, C, R> ExpiringRandMap<V, C, R> {
+    /// Creates an empty map using `clock`, and drawing handles from `rng`.
+    #[inline]
+    pub fn with_clock_and_rng(clock: C, rng: R) -> Self {
+        Self {
+            map: RandMap::with_rng(rng),
+            deadlines: HashMap::default(),
+            clock,
+        }
+    }
+
+    #[inline]
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.deadlines.clear();
+    }
+
+    /// Whether the map is empty, counting expired items not yet removed.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// The number of items, counting expired items not yet removed.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+impl<V, C, R> ExpiringRandMap<V, C, R>
+where
+    C: Clock,
+{
+    /// Whether `handle` refers to an item that has not expired.
+    #[inline]
+    pub fn contains(&self, handle: Handle<V>) -> bool {
+        self.get(handle).is_some()
+    }
+
+    /// Retrieves a reference to a `V`, unless it has expired.
+    #[inline]
+    pub fn get(&self, handle: Handle<V>) -> Option<&V> {
+        if self.is_expired(handle, self.clock.now()) {
+            None
+        } else {
+            self.map.get(handle)
+        }
+    }
+
+    /// Retrieves a mutable reference to a `V`, unless it has expired, in
+    /// which case it is removed.
+    #[inline]
+    pub fn get_mut(&mut self, handle: Handle<V>) -> Option<&mut V> {
+        if self.is_expired(handle, self.clock.now()) {
+            self.deadlines.remove(&handle);
+            self.map.remove(handle);
+            None
+        } else {
+            self.map.get_mut(handle)
+        }
+    }
+
+    /// An iterator over the items that have not expired. The iterator
+    /// element type is `(Handle<V>, &V)`.
+    pub fn iter(&self) -> ExpiringIter<'_, V> {
+        ExpiringIter {
+            items: self.map.iter(),
+            deadlines: &self.deadlines,
+            now: self.clock.now(),
+        }
+    }
+
+    /// Removes all expired items, and returns them.
+    pub fn purge_expired(&mut self) -> Vec<(Handle<V>, V)> {
+        let now = self.clock.now();
+        let mut expired = Vec::new();
+        let map = &mut self.map;
+        self.deadlines.retain(|&handle, &mut deadline| {
+            if deadline > now {
+                return true;
+            }
+            if let Some(value) = map.remove(handle) {
+                expired.push((handle, value));
+            }
+            false
+        });
+        expired
+    }
+
+    /// Remove and return the value with handle `handle`, or `None` if not
+    /// found or expired.
+    #[inline]
+    pub fn remove(&mut self, handle: Handle<V>) -> Option<V> {
+        let expired = self.is_expired(handle, self.clock.now());
+        self.deadlines.remove(&handle);
+        let value = self.map.remove(handle);
+        if expired {
+            None
+        } else {
+            value
+        }
+    }
+
+    /// Sets the time to live of an item that has not expired, or makes it
+    /// live indefinitely if `ttl` is `None`. Returns `false` if the item was
+    /// not found.
+    pub fn set_ttl(
+        &mut self,
+        handle: Handle<V>,
+        ttl: Option<Duration>,
+    ) -> bool {
+        if !self.contains(handle) {
+            return false;
+        }
+        match ttl.and_then(|ttl| self.deadline(ttl)) {
+            Some(deadline) => self.deadlines.insert(handle, deadline),
+            None => self.deadlines.remove(&handle),
+        };
+        true
+    }
+
+    /// The time an item has left to live, or `None` if it lives
+    /// indefinitely, has expired or is not found.
+    #[inline]
+    pub fn ttl(&self, handle: Handle<V>) -> Option<Duration> {
+        let deadline = *self.deadlines.get(&handle)?;
+        let now = self.clock.now();
+        if deadline > now {
+            Some(deadline - now)
+        } else {
+            None
+        }
+    }
+
+    // A `ttl` too long to be represented means no expiry at all.
+    #[inline]
+    fn deadline(&self, ttl: Duration) -> Option<Instant> {
+        self.clock.now().checked_add(ttl)
+    }
+
+    #[inline]
+    fn is_expired(&self, handle: Handle<V>, now: Instant) -> bool {
+        is_expired(&self.deadlines, handle, now)
+    }
+}
+
+impl<V, C, R> ExpiringRandMap<V, C, R>
+where
+    C: Clock,
+    R: RngCore,
+{
+    /// Insert a `V` that lives until removed, and get a handle for
+    /// retrieval.
+    #[inline]
+    pub fn insert(&mut self, value: V) -> Handle<V> {
+        self.map.insert(value)
+    }
+
+    /// Insert a `V` that expires after `ttl`, and get a handle for
+    /// retrieval.
+    pub fn insert_with_ttl(&mut self, value: V, ttl: Duration) -> Handle<V> {
+        let handle = self.map.insert(value);
+        if let Some(deadline) = self.deadline(ttl) {
+            self.deadlines.insert(handle, deadline);
+        }
+        handle
+    }
+}
+
+impl<V, C, R> Default for ExpiringRandMap<V, C, R>
+where
+    C: Default,
+    R: Default,
+{
+    fn default() -> Self {
+        Self::with_clock_and_rng(C::default(), R::default())
+    }
+}
+
+impl<'a, V, C, R> IntoIterator for &'a ExpiringRandMap<V, C, R>
+where
+    C: Clock,
+{
+    type Item = (Handle<V>, &'a V);
+    type IntoIter = ExpiringIter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// The type returned by [`ExpiringRandMap::iter()`
+/// ](struct.ExpiringRandMap.html#method.iter).
+///
+pub struct ExpiringIter<'a, V> {
+    items: Iter<'a, V>,
+    deadlines: &'a Deadlines<V>,
+    now: Instant,
+}
+
+impl<'a, V> Iterator for ExpiringIter<'a, V> {
+    type Item = (Handle<V>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (deadlines, now) = (self.deadlines, self.now);
+        self.items
+            .find(|&(handle, _)| !is_expired(deadlines, handle, now))
+    }
+}
+
+#[inline]
+fn is_expired<V>(
+    deadlines: &Deadlines<V>,
+    handle: Handle<V>,
+    now: Instant,
+) -> bool {
+    let deadline = deadlines.get(&handle);
+    matches!(deadline, Some(&deadline) if deadline <= now)
+}
@@ -0,0 +1,303 @@
+//! A map with generational handles, see [`GenRandMap`
+//! ](../struct.GenRandMap.html).
+
+use crate::{scramble, unscramble, DefaultRng, Handle};
+use hashers::null::PassThroughHasher;
+use rand::{Rng, RngCore};
+use std::collections::hash_map::{self, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::hash::BuildHasherDefault;
+
+const GENERATION_BITS: u32 = 16;
+const GENERATION_MASK: u64 = (1 << GENERATION_BITS) - 1;
+
+/// A [`RandMap`](struct.RandMap.html) look-alike whose handles detect that
+/// the item they refer to has been removed.
+///
+/// Each handle consists of 48 random bits identifying a slot and a 16 bit
+/// generation counter. Removing an item, or overwriting it using
+/// [`insert_key_value()`](#method.insert_key_value), bumps the generation of
+/// its slot, so any handle to the old item gets a [`StaleHandle`
+/// ](struct.StaleHandle.html) error rather than someone else's item.
+///
+/// Removed slots are remembered in order to recognize stale handles. Use
+/// [`clear_removed()`](#method.clear_removed) to forget them. As the
+/// generation counter wraps after 65536 generations, a handle that is that
+/// much out of date may erroneously be taken as current.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{GenRandMap, Handle, StaleHandle};
+///
+/// let mut map = GenRandMap::new();
+/// let foo = map.insert("foo");
+/// assert_eq!(map.get(foo), Ok(Some(&"foo")));
+/// assert_eq!(map.remove(foo), Ok(Some("foo")));
+/// assert_eq!(map.get(foo), Err(StaleHandle));
+/// // Reusing the number of the removed handle does not resurrect it.
+/// let bar = map.insert_key_value(Handle::from_u64(foo.as_u64()), "bar");
+/// assert_ne!(bar, foo);
+/// assert_eq!(map.get(foo), Err(StaleHandle));
+/// assert_eq!(map.get(bar), Ok(Some(&"bar")));
+/// assert_eq!(map.len(), 1);
+/// assert_eq!(map.get(Handle::from_u64(4711 << 16)), Ok(None));
+/// ```
+#[derive(Clone, Debug)]
+pub struct GenRandMap<V, R = DefaultRng> {
+    // Keyed by the scrambled slot bits of the handles, see `slot_key()`.
+    slots: HashMap<u64, Slot<V>, BuildHasherDefault<PassThroughHasher>>,
+    len: usize,
+    rng: R,
+}
+
+#[derive(Clone, Debug)]
+struct Slot<V> {
+    generation: u64,
+    value: Option<V>,
+}
+
+impl<V> GenRandMap<V> {
+    /// Creates an empty map.
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_rng(DefaultRng)
+    }
+}
+
+impl<V, R> GenRandMap<V, R> {
+    /// Creates an empty map that draws its handles from `rng`.
+    #[inline]
+    pub fn with_rng(rng: R) -> Self {
+        Self {
+            slots: HashMap::default(),
+            len: 0,
+            rng,
+        }
+    }
+
+    /// Clears the map. Afterwards, handles of the removed items are no
+    /// longer recognized as stale.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.len = 0;
+    }
+
+    /// Forgets the slots of removed items, so that their handles are no
+    /// longer recognized as stale.
+    #[inline]
+    pub fn clear_removed(&mut self) {
+        self.slots.retain(|_, slot| slot.value.is_some());
+    }
+
+    /// Retrieves a reference to a `V`.
+    ///
+    /// Returns `Ok(None)` if `handle` is unknown, and `Err(StaleHandle)` if
+    /// the item it referred to has been removed or replaced.
+    #[inline]
+    pub fn get(&self, handle: Handle<V>) -> Result<Option<&V>, StaleHandle> {
+        match self.slots.get(&slot_key(handle)) {
+            None => Ok(None),
+            Some(slot) => slot.current(handle).map(Some),
+        }
+    }
+
+    /// Retrieves a mutable reference to a `V`. See [`get()`
+    /// ](#method.get).
+    #[inline]
+    pub fn get_mut(
+        &mut self,
+        handle: Handle<V>,
+    ) -> Result<Option<&mut V>, StaleHandle> {
+        match self.slots.get_mut(&slot_key(handle)) {
+            None => Ok(None),
+            Some(slot) => slot.current_mut(handle).map(Some),
+        }
+    }
+
+    /// Insert a value with a handle of your choice. Returns the handle that
+    /// is actually valid for `value`.
+    ///
+    /// If `handle` is current, its item is replaced and `handle` returned.
+    /// Otherwise the slot of `handle` gets a fresh generation, so that no
+    /// handle that has been in use refers to `value`.
+    pub fn insert_key_value(
+        &mut self,
+        handle: Handle<V>,
+        value: V,
+    ) -> Handle<V> {
+        let key = slot_key(handle);
+        match self.slots.entry(key) {
+            hash_map::Entry::Vacant(entry) => {
+                entry.insert(Slot {
+                    generation: generation(handle),
+                    value: Some(value),
+                });
+                self.len += 1;
+                handle
+            }
+            hash_map::Entry::Occupied(mut entry) => {
+                let slot = entry.get_mut();
+                if slot.value.is_some() {
+                    if slot.generation != generation(handle) {
+                        slot.generation = next_generation(slot.generation);
+                    }
+                } else {
+                    self.len += 1;
+                }
+                slot.value = Some(value);
+                handle_of(key, slot.generation)
+            }
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The iterator element type is `(Handle<V>, &V)`.
+    #[inline]
+    pub fn iter(&self) -> GenIter<'_, V> {
+        GenIter(self.slots.iter())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Remove and return the value with handle `handle`, or `None` if not
+    /// found. Afterwards, `handle` is stale.
+    pub fn remove(
+        &mut self,
+        handle: Handle<V>,
+    ) -> Result<Option<V>, StaleHandle> {
+        match self.slots.get_mut(&slot_key(handle)) {
+            None => Ok(None),
+            Some(slot) => {
+                slot.current(handle)?;
+                slot.generation = next_generation(slot.generation);
+                self.len -= 1;
+                Ok(slot.value.take())
+            }
+        }
+    }
+}
+
+impl<V, R> GenRandMap<V, R>
+where
+    R: RngCore,
+{
+    /// Insert a `V` and get a handle for retrieval. The handle is guaranteed
+    /// to refer to a slot never used before.
+    pub fn insert(&mut self, value: V) -> Handle<V> {
+        loop {
+            let key = scramble(self.rng.gen::<u64>() >> GENERATION_BITS);
+            if let hash_map::Entry::Vacant(entry) = self.slots.entry(key) {
+                entry.insert(Slot {
+                    generation: 0,
+                    value: Some(value),
+                });
+                self.len += 1;
+                return handle_of(key, 0);
+            }
+        }
+    }
+}
+
+impl<V, R> Default for GenRandMap<V, R>
+where
+    R: Default,
+{
+    fn default() -> Self {
+        Self::with_rng(R::default())
+    }
+}
+
+impl<'a, V, R> IntoIterator for &'a GenRandMap<V, R> {
+    type Item = (Handle<V>, &'a V);
+    type IntoIter = GenIter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<V> Slot<V> {
+    fn current(&self, handle: Handle<V>) -> Result<&V, StaleHandle> {
+        match self.value {
+            Some(ref value) if self.generation == generation(handle) => {
+                Ok(value)
+            }
+            _ => Err(StaleHandle),
+        }
+    }
+
+    fn current_mut(
+        &mut self,
+        handle: Handle<V>,
+    ) -> Result<&mut V, StaleHandle> {
+        match self.value {
+            Some(ref mut value) if self.generation == generation(handle) => {
+                Ok(value)
+            }
+            _ => Err(StaleHandle),
+        }
+    }
+}
+
+/// The type returned by [`GenRandMap::iter()`
+/// ](struct.GenRandMap.html#method.iter).
+///
+pub struct GenIter<'a, V>(hash_map::Iter<'a, u64, Slot<V>>);
+impl<'a, V> Iterator for GenIter<'a, V> {
+    type Item = (Handle<V>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (key, slot) in &mut self.0 {
+            if let Some(ref value) = slot.value {
+                return Some((handle_of(*key, slot.generation), value));
+            }
+        }
+        None
+    }
+}
+
+/// The error returned when using a handle to an item that has been removed
+/// from a [`GenRandMap`](struct.GenRandMap.html).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StaleHandle;
+
+impl fmt::Display for StaleHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("stale handle")
+    }
+}
+
+impl Error for StaleHandle {}
+
+#[inline]
+fn generation<V>(handle: Handle<V>) -> u64 {
+    handle.as_u64() & GENERATION_MASK
+}
+
+#[inline]
+fn next_generation(generation: u64) -> u64 {
+    (generation + 1) & GENERATION_MASK
+}
+
+// The key of the slot of `handle`. The slot bits are scrambled, as the
+// constant low bits would leave the hash table nothing to tell keys apart
+// by before comparing them.
+#[inline]
+fn slot_key<V>(handle: Handle<V>) -> u64 {
+    scramble(handle.as_u64() >> GENERATION_BITS)
+}
+
+// The inverse of `slot_key()`, plus the generation.
+#[inline]
+fn handle_of<V>(key: u64, generation: u64) -> Handle<V> {
+    Handle::from_u64(unscramble(key) << GENERATION_BITS | generation)
+}
@@ -0,0 +1,370 @@
+//! A map iterated in insertion order, see [`IndexRandMap`
+//! ](../struct.IndexRandMap.html).
+
+use crate::{
+    DefaultRng, Handle, HandleKey, SlabIntoIter, SlabIter, SlabIterMut,
+    SlabRandMap,
+};
+use rand::{Rng, RngCore};
+use std::iter::FromIterator;
+
+/// A [`RandMap`](struct.RandMap.html) look-alike that remembers the order
+/// of insertion, like the `IndexMap` of the `indexmap` crate.
+///
+/// Iteration yields the items in the order they were inserted, whatever
+/// their handles, so listings come out the same on every run. Besides by
+/// handle, items can be accessed by position.
+///
+/// There are two ways to remove an item: [`shift_remove()`
+/// ](#method.shift_remove) keeps the order of the other items, but takes
+/// time proportional to their number, while [`swap_remove()`
+/// ](#method.swap_remove) moves the last item into the gap, in constant
+/// time.
+///
+/// The map is a [`SlabRandMap`](struct.SlabRandMap.html) that keeps its
+/// slab in order, and shares its layout and performance.
+///
+/// ### Example:
+/// ```
+/// use rand_map::IndexRandMap;
+///
+/// let mut map = IndexRandMap::new();
+/// let handles: Vec<_> = ["a", "b", "c", "d"]
+///     .iter()
+///     .map(|&value| map.insert(value))
+///     .collect();
+/// let values = |map: &IndexRandMap<_>| {
+///     map.iter().map(|(_, &v)| v).collect::<Vec<_>>()
+/// };
+/// assert_eq!(values(&map), ["a", "b", "c", "d"]);
+/// assert_eq!(map.get_index(1), Some((handles[1], &"b")));
+/// assert_eq!(map.index_of(handles[2]), Some(2));
+///
+/// assert_eq!(map.shift_remove(handles[0]), Some("a"));
+/// assert_eq!(values(&map), ["b", "c", "d"]);
+/// assert_eq!(map.swap_remove(handles[1]), Some("b"));
+/// assert_eq!(values(&map), ["d", "c"]);
+/// assert_eq!(map.get(handles[3]), Some(&"d"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct IndexRandMap<V, R = DefaultRng, K = u64>(SlabRandMap<V, R, K>);
+
+impl<V> IndexRandMap<V> {
+    /// Creates an empty map.
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_rng(DefaultRng)
+    }
+}
+
+impl<V, R> IndexRandMap<V, R> {
+    /// Creates an empty map that draws its handles from `rng`.
+    #[inline]
+    pub fn with_rng(rng: R) -> Self {
+        Self::from_rng(rng)
+    }
+}
+
+impl<V, R, K> IndexRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    /// Like [`with_rng()`](#method.with_rng), but for any handle width `K`.
+    #[inline]
+    pub fn from_rng(rng: R) -> Self {
+        Self(SlabRandMap::from_rng(rng))
+    }
+
+    #[inline]
+    pub fn rng(&self) -> &R {
+        self.0.rng()
+    }
+
+    #[inline]
+    pub fn rng_mut(&mut self) -> &mut R {
+        self.0.rng_mut()
+    }
+
+    /// Borrow the contained [`SlabRandMap`](struct.SlabRandMap.html), whose
+    /// slab order is the order of this map.
+    #[inline]
+    pub fn as_slab(&self) -> &SlabRandMap<V, R, K> {
+        &self.0
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    #[inline]
+    pub fn get(&self, handle: Handle<V, K>) -> Option<&V> {
+        self.0.get(handle)
+    }
+
+    /// The item at position `index`, and its handle.
+    #[inline]
+    pub fn get_index(&self, index: usize) -> Option<(Handle<V, K>, &V)> {
+        Some((*self.0.handles.get(index)?, &self.0.values[index]))
+    }
+
+    /// The item at position `index`, and its handle.
+    #[inline]
+    pub fn get_index_mut(
+        &mut self,
+        index: usize,
+    ) -> Option<(Handle<V, K>, &mut V)> {
+        Some((*self.0.handles.get(index)?, &mut self.0.values[index]))
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, handle: Handle<V, K>) -> Option<&mut V> {
+        self.0.get_mut(handle)
+    }
+
+    /// The position of the item with handle `handle`.
+    #[inline]
+    pub fn index_of(&self, handle: Handle<V, K>) -> Option<usize> {
+        self.0.index.get(&handle).copied()
+    }
+
+    /// Like [`insert()`](#method.insert), but draws the handle from `rng`
+    /// rather than from the map's own random source.
+    #[inline]
+    pub fn insert_with_rng<G>(
+        &mut self,
+        value: V,
+        rng: &mut G,
+    ) -> Handle<V, K>
+    where
+        G: Rng + ?Sized,
+    {
+        self.0.insert_with_rng(value, rng)
+    }
+
+    /// Insert a key-value pair. An existing item with handle `key` is
+    /// replaced, keeping its position, otherwise the item goes last.
+    #[inline]
+    pub fn insert_key_value(&mut self, key: Handle<V, K>, value: V) {
+        self.0.insert_key_value(key, value)
+    }
+
+    /// Insert a key-value pair and return the value previously stored under
+    /// `key`, if any. See [`insert_key_value()`](#method.insert_key_value).
+    #[inline]
+    pub fn replace_key_value(
+        &mut self,
+        key: Handle<V, K>,
+        value: V,
+    ) -> Option<V> {
+        self.0.replace_key_value(key, value)
+    }
+
+    /// Insert a key-value pair unless `key` is already present, in which
+    /// case the map is left untouched and `value` is given back.
+    #[inline]
+    pub fn try_insert_key_value(
+        &mut self,
+        key: Handle<V, K>,
+        value: V,
+    ) -> Result<(), V> {
+        self.0.try_insert_key_value(key, value)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The iterator element type is `(Handle<V, K>, &V)`, in order.
+    #[inline]
+    pub fn iter(&self) -> IndexIter<'_, V, K> {
+        self.0.iter()
+    }
+
+    /// The iterator element type is `(Handle<V, K>, &mut V)`, in order.
+    #[inline]
+    pub fn iter_mut(&mut self) -> IndexIterMut<'_, V, K> {
+        self.0.iter_mut()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Retains only the items for which `f` returns `true`, keeping their
+    /// order.
+    #[inline]
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(Handle<V, K>, &mut V) -> bool,
+    {
+        self.0.retain(f)
+    }
+
+    /// Remove and return the value with handle `handle`, or `None` if not
+    /// found. The following items move up, keeping their order.
+    #[inline]
+    pub fn shift_remove(&mut self, handle: Handle<V, K>) -> Option<V> {
+        let index = self.index_of(handle)?;
+        self.shift_remove_index(index).map(|(_, value)| value)
+    }
+
+    /// Remove and return the item at position `index`, and its handle. See
+    /// [`shift_remove()`](#method.shift_remove).
+    pub fn shift_remove_index(
+        &mut self,
+        index: usize,
+    ) -> Option<(Handle<V, K>, V)> {
+        if index >= self.0.len() {
+            return None;
+        }
+        let handle = self.0.handles.remove(index);
+        self.0.index.remove(&handle);
+        let value = self.0.values.remove(index);
+        self.0.reindex(index);
+        Some((handle, value))
+    }
+
+    /// Remove and return the value with handle `handle`, or `None` if not
+    /// found. The last item takes its place.
+    #[inline]
+    pub fn swap_remove(&mut self, handle: Handle<V, K>) -> Option<V> {
+        self.0.remove(handle)
+    }
+
+    /// Remove and return the item at position `index`, and its handle. See
+    /// [`swap_remove()`](#method.swap_remove).
+    #[inline]
+    pub fn swap_remove_index(
+        &mut self,
+        index: usize,
+    ) -> Option<(Handle<V, K>, V)> {
+        self.0.swap_remove_index(index)
+    }
+}
+
+impl<V, R, K> IndexRandMap<V, R, K>
+where
+    R: RngCore,
+    K: HandleKey,
+{
+    /// Insert a `V` and get a handle for retrieval. The item goes last.
+    ///
+    /// # Panics
+    ///
+    /// Panics if every possible handle is in use.
+    #[inline]
+    pub fn insert(&mut self, value: V) -> Handle<V, K> {
+        self.0.insert(value)
+    }
+}
+
+impl<V, R, K> Default for IndexRandMap<V, R, K>
+where
+    R: Default,
+    K: HandleKey,
+{
+    fn default() -> Self {
+        Self::from_rng(R::default())
+    }
+}
+
+impl<'a, V, R, K> IntoIterator for &'a IndexRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, &'a V);
+    type IntoIter = IndexIter<'a, V, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, V, R, K> IntoIterator for &'a mut IndexRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, &'a mut V);
+    type IntoIter = IndexIterMut<'a, V, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// Consumes the map, the iterator element type is `(Handle<V, K>, V)`, in
+/// order.
+///
+impl<V, R, K> IntoIterator for IndexRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, V);
+    type IntoIter = IndexIntoIter<V, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// Like [`insert_key_value()`
+/// ](struct.IndexRandMap.html#method.insert_key_value), an existing item
+/// with the same handle is overwritten in place.
+impl<V, R, K> Extend<(Handle<V, K>, V)> for IndexRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    fn extend<I: IntoIterator<Item = (Handle<V, K>, V)>>(&mut self, iter: I) {
+        self.0.extend(iter)
+    }
+}
+
+/// The map gets `R::default()` as random source.
+impl<V, R, K> FromIterator<(Handle<V, K>, V)> for IndexRandMap<V, R, K>
+where
+    R: Default,
+    K: HandleKey,
+{
+    fn from_iter<I: IntoIterator<Item = (Handle<V, K>, V)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+/// Keeps the handles, the order and the random source.
+impl<V, R, K> From<SlabRandMap<V, R, K>> for IndexRandMap<V, R, K> {
+    fn from(slab: SlabRandMap<V, R, K>) -> Self {
+        Self(slab)
+    }
+}
+
+/// Keeps the handles, the order and the random source.
+impl<V, R, K> From<IndexRandMap<V, R, K>> for SlabRandMap<V, R, K> {
+    fn from(map: IndexRandMap<V, R, K>) -> Self {
+        map.0
+    }
+}
+
+/// The items and their order are compared, not the random sources.
+impl<V, R, K> PartialEq for IndexRandMap<V, R, K>
+where
+    V: PartialEq,
+    K: HandleKey,
+{
+    fn eq(&self, other: &IndexRandMap<V, R, K>) -> bool {
+        self.0.handles == other.0.handles && self.0.values == other.0.values
+    }
+}
+
+/// The type returned by [`IndexRandMap::iter()`
+/// ](struct.IndexRandMap.html#method.iter).
+pub type IndexIter<'a, V, K = u64> = SlabIter<'a, V, K>;
+
+/// The type returned by [`IndexRandMap::iter_mut()`
+/// ](struct.IndexRandMap.html#method.iter_mut).
+pub type IndexIterMut<'a, V, K = u64> = SlabIterMut<'a, V, K>;
+
+/// The consuming iterator of an [`IndexRandMap`](struct.IndexRandMap.html).
+pub type IndexIntoIter<V, K = u64> = SlabIntoIter<V, K>;
@@ -0,0 +1,98 @@
+//! The raw representations of [`Handle`](../struct.Handle.html)s.
+
+use rand::Rng;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for u32 {}
+    impl Sealed for u64 {}
+    impl Sealed for u128 {}
+}
+
+/// The raw representation of a [`Handle`](struct.Handle.html), i.e. its
+/// width. Implemented for `u32`, `u64`, the default, and `u128`.
+///
+/// A `u32` handle saves space in small maps. Note though that collisions
+/// between random `u32`s are common, by the birthday bound they are likely
+/// already at some 77000 items. A [`RandMap`](struct.RandMap.html) never
+/// overwrites an item on a collision but draws a new handle, so a collision
+/// costs but a retry. The expected number of draws per insertion is
+/// `1 / (1 - len / 2^32)`.
+///
+/// A `u128` handle drawn from a cryptographically secure generator is
+/// unguessable, see [`SecureRandMap`](type.SecureRandMap.html).
+///
+/// The pass-through hashing of the map needs the handle bits spread over a
+/// `u64`, so a `u32` is scrambled by a multiplication and the halves of a
+/// `u128` are combined.
+///
+/// This trait is sealed, it cannot be implemented outside this crate.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{DefaultRng, Handle, RandMap};
+///
+/// let mut map: RandMap<&str, DefaultRng, u32> = RandMap::default();
+/// let foo = map.insert("foo");
+/// assert_eq!(std::mem::size_of_val(&foo), 4);
+/// let raw: u32 = foo.into();
+/// assert_eq!(map.get(Handle::from_u32(raw)), Some(&"foo"));
+/// assert_eq!(foo.to_string().parse(), Ok(foo));
+/// ```
+pub trait HandleKey:
+    private::Sealed + Copy + Debug + Eq + Hash + Ord + Send + Sync + 'static
+{
+    #[doc(hidden)]
+    const MAX: u128;
+
+    #[doc(hidden)]
+    fn from_u128(u: u128) -> Option<Self>;
+
+    // The bits fed to the pass-through hasher.
+    #[doc(hidden)]
+    fn hash_bits(self) -> u64;
+
+    #[doc(hidden)]
+    fn random<R: Rng + ?Sized>(rng: &mut R) -> Self;
+
+    #[doc(hidden)]
+    fn to_u128(self) -> u128;
+}
+
+macro_rules! handle_key {
+    ($t:ty, $u:ident => $hash_bits:expr) => {
+        impl HandleKey for $t {
+            const MAX: u128 = <$t>::MAX as u128;
+
+            #[inline]
+            fn from_u128(u: u128) -> Option<Self> {
+                use std::convert::TryFrom;
+                Self::try_from(u).ok()
+            }
+
+            #[inline]
+            fn hash_bits(self) -> u64 {
+                let $u = self;
+                $hash_bits
+            }
+
+            #[inline]
+            fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+                rng.gen()
+            }
+
+            #[inline]
+            fn to_u128(self) -> u128 {
+                self as u128
+            }
+        }
+    };
+}
+
+// Scrambled, which is a bijection that moves the bits up.
+handle_key!(u32, u => crate::scramble(u as u64));
+handle_key!(u64, u => u);
+handle_key!(u128, u => (u ^ u >> 64) as u64);
@@ -1,11 +1,64 @@
 //! A map that creates a random handle on insertion to use when retrieving.
 
+mod bounded;
+mod builder;
+mod concurrent;
+mod durable;
+mod encoding;
+mod entry;
+mod expiring;
+mod generational;
+mod indexed;
+mod key;
+#[cfg(feature = "lock_free")]
+mod lock_free;
+mod ordered;
+mod permuted;
+mod registry;
+#[cfg(feature = "signed")]
+mod signed;
+mod slab;
+mod snapshot;
+mod tagged;
+
+pub use bounded::{BoundedRandMap, EvictionPolicy, Lfu, Lru};
+pub use builder::RandMapBuilder;
+pub use concurrent::ConcurrentRandMap;
+pub use durable::{DurableRandMap, DurableRefMut, WalError};
+pub use encoding::{HandleEncoding, ParseHandleError};
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use expiring::{
+    Clock, ExpiringIter, ExpiringRandMap, ManualClock, SystemClock,
+};
+pub use generational::{GenIter, GenRandMap, StaleHandle};
+pub use indexed::{IndexIntoIter, IndexIter, IndexIterMut, IndexRandMap};
+pub use key::HandleKey;
+#[cfg(feature = "lock_free")]
+pub use lock_free::LockFreeRandMap;
+pub use ordered::{
+    BTreeIntoIter, BTreeIter, BTreeIterMut, BTreeRandMap, BTreeRange,
+    BTreeRangeMut,
+};
+pub use permuted::{
+    PermutedDrain, PermutedIntoIter, PermutedIter, PermutedIterMut,
+    PermutedRandMap,
+};
+pub use registry::{RandRegistry, RegistryIter};
+#[cfg(feature = "signed")]
+pub use signed::{SignedRandMap, VerifyError};
+pub use slab::{SlabDrain, SlabIntoIter, SlabIter, SlabIterMut, SlabRandMap};
+pub use snapshot::{SnapshotError, SnapshotValue};
+pub use tagged::{TaggedIter, TaggedRandMap, WrongMap};
+
 use hashers::null::PassThroughHasher;
+use rand::{Rng, RngCore};
 #[cfg(feature = "serialize")]
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp::Ordering;
 use std::collections::hash_map::{self, HashMap};
+use std::collections::TryReserveError;
 use std::hash::{BuildHasherDefault, Hash, Hasher};
+use std::iter::FromIterator;
 use std::marker::PhantomData;
 
 /// A map that creates a random handle on insertion to use when retrieving.
@@ -72,17 +125,101 @@ use std::marker::PhantomData;
 /// assert!(map.as_hash_map().contains_key(&bar));
 /// assert!(map == map.clone());
 /// ```
+///
+/// Handles are drawn from the random source `R`, which defaults to
+/// [`DefaultRng`](struct.DefaultRng.html), i.e. `rand::thread_rng()`. Use
+/// [`with_rng()`](#method.with_rng) to supply a generator of your own, e.g. a
+/// seeded one for reproducible handle sequences.
+///
+/// With the `serialize` feature the map is serialized as a map from handles
+/// to items, e.g. a JSON object keyed by the handles as decimal strings. The
+/// random source is not serialized, a deserialized map gets `R::default()`.
+///
+/// The handles are `u64`s by default. Other widths are available through the
+/// type parameter `K`, see [`HandleKey`](trait.HandleKey.html).
 #[derive(Clone, Debug)]
-#[cfg_attr(feature = "serialize", derive(Deserialize, Serialize))]
-pub struct RandMap<V>(
-    HashMap<Handle<V>, V, BuildHasherDefault<PassThroughHasher>>
+pub struct RandMap<V, R = DefaultRng, K = u64>(
+    HashMap<Handle<V, K>, V, BuildHasherDefault<PassThroughHasher>>,
+    R,
 );
 
 impl<V> RandMap<V> {
     /// Creates an empty map.
     #[inline]
     pub fn new() -> Self {
-        Self(HashMap::default())
+        Self(HashMap::default(), DefaultRng)
+    }
+
+    /// Creates an empty map with room for at least `capacity` items before
+    /// it reallocates.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self(
+            HashMap::with_capacity_and_hasher(capacity, Default::default()),
+            DefaultRng,
+        )
+    }
+
+    /// Configure the capacity, the random source and the handle width of a
+    /// new map, see [`RandMapBuilder`](struct.RandMapBuilder.html).
+    #[inline]
+    pub fn builder() -> RandMapBuilder<V> {
+        RandMapBuilder::new()
+    }
+}
+
+impl<V, R> RandMap<V, R> {
+    /// Creates an empty map that draws its handles from `rng`.
+    ///
+    /// ### Example:
+    /// ```
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// use rand_map::RandMap;
+    ///
+    /// let mut map1 = RandMap::with_rng(StdRng::seed_from_u64(4711));
+    /// let mut map2 = RandMap::with_rng(StdRng::seed_from_u64(4711));
+    /// for value in 0..10 {
+    ///     assert_eq!(map1.insert(value), map2.insert(value));
+    /// }
+    /// ```
+    #[inline]
+    pub fn with_rng(rng: R) -> Self {
+        Self(HashMap::default(), rng)
+    }
+}
+
+impl<V, R, K> RandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    /// Like [`with_rng()`](#method.with_rng), but for any handle width `K`,
+    /// which must then be known from the context.
+    ///
+    /// ### Example:
+    /// ```
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// use rand_map::RandMap;
+    ///
+    /// let mut map: RandMap<_, _, u128> =
+    ///     RandMap::from_rng(StdRng::seed_from_u64(4711));
+    /// let foo = map.insert("foo");
+    /// assert_eq!(map.get(foo), Some(&"foo"));
+    /// ```
+    #[inline]
+    pub fn from_rng(rng: R) -> Self {
+        Self(HashMap::default(), rng)
+    }
+
+    /// Borrow the random source.
+    #[inline]
+    pub fn rng(&self) -> &R {
+        &self.1
+    }
+
+    /// Mutably borrow the random source, e.g. to reseed it.
+    #[inline]
+    pub fn rng_mut(&mut self) -> &mut R {
+        &mut self.1
     }
 
     /// Borrow the contained [`HashMap`
@@ -90,58 +227,195 @@ impl<V> RandMap<V> {
     #[inline]
     pub fn as_hash_map(
         &self,
-    ) -> &HashMap<Handle<V>, V, BuildHasherDefault<PassThroughHasher>> {
+    ) -> &HashMap<Handle<V, K>, V, BuildHasherDefault<PassThroughHasher>>
+    {
         &self.0
     }
 
+    /// The number of items the map can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
     /// Clears the map.
     #[inline]
     pub fn clear(&mut self) {
         self.0.clear()
     }
 
+    /// Gets the entry for `handle` for in-place manipulation.
+    #[inline]
+    pub fn entry(&mut self, handle: Handle<V, K>) -> Entry<'_, V, K> {
+        Entry::new(self.0.entry(handle))
+    }
+
     /// Retrieves a reference to a `V` using the handle created by [`insert()`
     /// ](#method.insert).
     #[inline]
-    pub fn get(&self, handle: Handle<V>) -> Option<&V> {
+    pub fn get(&self, handle: Handle<V, K>) -> Option<&V> {
         self.0.get(&handle)
     }
 
     /// Retrieves a mutable reference to a `V` using the handle created by
     /// [`insert()`](#method.insert).
     #[inline]
-    pub fn get_mut(&mut self, handle: Handle<V>) -> Option<&mut V> {
+    pub fn get_mut(&mut self, handle: Handle<V, K>) -> Option<&mut V> {
         self.0.get_mut(&handle)
     }
 
-    /// Insert a `V` and get a handle for retrieval.
+    /// Like [`insert()`](#method.insert), but draws the handle from `rng`
+    /// rather than from the map's own random source.
     ///
-    pub fn insert(&mut self, value: V) -> Handle<V> {
-        use rand::{thread_rng, Rng};
-        let key: Handle<V> = thread_rng().gen();
-        self.0.insert(key, value);
-        key
+    /// ### Example:
+    /// ```
+    /// use rand::rngs::mock::StepRng;
+    /// use rand_map::{Handle, RandMap};
+    ///
+    /// let mut map = RandMap::new();
+    /// map.insert_key_value(Handle::from_u64(1), "one");
+    /// // A rigged generator yielding 1, 2, 3, ...
+    /// let mut rng = StepRng::new(1, 1);
+    /// let two = map.insert_with_rng("two", &mut rng);
+    /// assert_eq!(two.as_u64(), 2);
+    /// assert_eq!(map.get(Handle::from_u64(1)), Some(&"one"));
+    /// ```
+    pub fn insert_with_rng<G>(
+        &mut self,
+        value: V,
+        rng: &mut G,
+    ) -> Handle<V, K>
+    where
+        G: Rng + ?Sized,
+    {
+        insert_fresh(&mut self.0, value, rng)
     }
 
-    /// Insert a key-value pair. Does *not* return the old value for `key`.
+    /// Insert a key-value pair. Does *not* return the old value for `key`,
+    /// use [`replace_key_value()`](#method.replace_key_value) or
+    /// [`try_insert_key_value()`](#method.try_insert_key_value) if that
+    /// matters.
     ///
-    pub fn insert_key_value(&mut self, key: Handle<V>, value: V) {
+    pub fn insert_key_value(&mut self, key: Handle<V, K>, value: V) {
         self.0.insert(key, value);
     }
 
+    /// Insert a key-value pair and return the value previously stored under
+    /// `key`, if any.
+    ///
+    /// ### Example:
+    /// ```
+    /// use rand_map::{Handle, RandMap};
+    ///
+    /// let mut map = RandMap::new();
+    /// let key = Handle::from_u64(4711);
+    /// assert_eq!(map.replace_key_value(key, "foo"), None);
+    /// assert_eq!(map.replace_key_value(key, "bar"), Some("foo"));
+    /// assert_eq!(map.get(key), Some(&"bar"));
+    /// ```
+    #[inline]
+    pub fn replace_key_value(
+        &mut self,
+        key: Handle<V, K>,
+        value: V,
+    ) -> Option<V> {
+        self.0.insert(key, value)
+    }
+
+    /// Insert a key-value pair unless `key` is already present, in which
+    /// case the map is left untouched and `value` is given back.
+    ///
+    /// ### Example:
+    /// ```
+    /// use rand_map::{Handle, RandMap};
+    ///
+    /// let mut map = RandMap::new();
+    /// let key = Handle::from_u64(4711);
+    /// assert_eq!(map.try_insert_key_value(key, "foo"), Ok(()));
+    /// assert_eq!(map.try_insert_key_value(key, "bar"), Err("bar"));
+    /// assert_eq!(map.get(key), Some(&"foo"));
+    /// ```
+    pub fn try_insert_key_value(
+        &mut self,
+        key: Handle<V, K>,
+        value: V,
+    ) -> Result<(), V> {
+        match self.0.entry(key) {
+            hash_map::Entry::Occupied(_) => Err(value),
+            hash_map::Entry::Vacant(entry) => {
+                entry.insert(value);
+                Ok(())
+            }
+        }
+    }
+
+    /// Clears the map, returning all handle-value pairs as an iterator. Keeps
+    /// the allocated memory for reuse.
+    ///
+    /// If the returned iterator is dropped before being fully consumed, it
+    /// drops the remaining pairs.
+    #[inline]
+    pub fn drain(&mut self) -> Drain<'_, V, K> {
+        Drain(self.0.drain())
+    }
+
+    /// Creates an iterator which uses a closure to determine if an item
+    /// should be removed. If the closure returns `true`, the item is removed
+    /// from the map and yielded as a handle-value pair. If the closure
+    /// returns `false`, the item remains in the map and is not yielded.
+    ///
+    /// If the returned iterator is not exhausted, the remaining items are
+    /// retained. Use [`retain()`](#method.retain) if you do not need the
+    /// removed items.
+    ///
+    /// ### Example:
+    /// ```
+    /// use rand_map::RandMap;
+    ///
+    /// let mut map = RandMap::new();
+    /// for value in 0..8 {
+    ///     map.insert(value);
+    /// }
+    /// let mut odd: Vec<_> = map.extract_if(|_, v| *v % 2 == 1)
+    ///     .map(|(_, v)| v)
+    ///     .collect();
+    /// odd.sort();
+    /// assert_eq!(odd, vec![1, 3, 5, 7]);
+    /// assert_eq!(map.len(), 4);
+    /// map.retain(|_, v| *v < 4);
+    /// let mut even: Vec<_> = map.into_iter().map(|(_, v)| v).collect();
+    /// even.sort();
+    /// assert_eq!(even, vec![0, 2]);
+    /// ```
+    #[inline]
+    pub fn extract_if<F>(
+        &mut self,
+        mut pred: F,
+    ) -> ExtractIf<'_, V, impl FnMut(&Handle<V, K>, &mut V) -> bool, K>
+    where
+        F: FnMut(Handle<V, K>, &mut V) -> bool,
+    {
+        ExtractIf(self.0.extract_if(move |k, v| pred(*k, v)))
+    }
+
     /// Almost equivalent to `as_hash_map().iter()`, but the iterator element
-    /// type is `(Handle<V>, &V)` rather than `(&Handle<V>, &V)`
+    /// type is `(Handle<V, K>, &V)` rather than `(&Handle<V, K>, &V)`
     #[inline]
-    pub fn iter(&self) -> Iter<V> {
+    pub fn iter(&self) -> Iter<'_, V, K> {
         Iter(self.0.iter())
     }
 
-    /// The iterator element type is `(Handle<V>, &mut V)`.
+    /// The iterator element type is `(Handle<V, K>, &mut V)`.
     #[inline]
-    pub fn iter_mut(&mut self) -> IterMut<V> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, V, K> {
         IterMut(self.0.iter_mut())
     }
 
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.0.len()
@@ -150,40 +424,369 @@ impl<V> RandMap<V> {
     /// Remove and return the value with handle `handle`, or `None` if not
     /// found.
     #[inline]
-    pub fn remove(&mut self, handle: Handle<V>) -> Option<V> {
+    pub fn remove(&mut self, handle: Handle<V, K>) -> Option<V> {
         self.0.remove(&handle)
     }
+
+    /// Retains only the items specified by the predicate, i.e. removes all
+    /// items for which `f(handle, &mut value)` returns `false`.
+    #[inline]
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Handle<V, K>, &mut V) -> bool,
+    {
+        self.0.retain(|k, v| f(*k, v))
+    }
+
+    /// Reserves room for at least `additional` more items, to avoid
+    /// repeated reallocation when inserting many.
+    ///
+    /// # Panics
+    ///
+    /// If the new capacity overflows `usize`. Allocation failure aborts, use
+    /// [`try_reserve()`](#method.try_reserve) to handle it instead.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.0.reserve(additional)
+    }
+
+    /// Shrinks the capacity as much as possible, but not below the length
+    /// and `min_capacity`.
+    #[inline]
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.0.shrink_to(min_capacity)
+    }
+
+    /// Shrinks the capacity as much as possible.
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.0.shrink_to_fit()
+    }
+
+    /// Like [`reserve()`](#method.reserve), but returns an error rather than
+    /// panicking or aborting if the room cannot be had. The map is left
+    /// untouched on error.
+    ///
+    /// ### Example:
+    /// ```
+    /// use rand_map::RandMap;
+    ///
+    /// let mut map = RandMap::new();
+    /// map.insert("foo");
+    /// assert!(map.try_reserve(100).is_ok());
+    /// assert!(map.capacity() >= 101);
+    /// assert!(map.try_reserve(usize::MAX).is_err());
+    /// assert_eq!(map.len(), 1);
+    /// map.shrink_to_fit();
+    /// assert!(map.capacity() < 101);
+    /// ```
+    #[inline]
+    pub fn try_reserve(
+        &mut self,
+        additional: usize,
+    ) -> Result<(), TryReserveError> {
+        self.0.try_reserve(additional)
+    }
+}
+
+impl<V, R, K> RandMap<V, R, K>
+where
+    R: RngCore,
+    K: HandleKey,
+{
+    /// Insert a `V` and get a handle for retrieval.
+    ///
+    /// The handle is guaranteed to be fresh, i.e. no existing item is ever
+    /// overwritten. Should the random handle collide with one already in the
+    /// map, a new one is drawn.
+    ///
+    /// # Panics
+    ///
+    /// If every possible handle is in use, which is conceivable for `u32`
+    /// handles only.
+    pub fn insert(&mut self, value: V) -> Handle<V, K> {
+        insert_fresh(&mut self.0, value, &mut self.1)
+    }
+
+    /// Insert all values of `values` and get their handles, in iteration
+    /// order.
+    ///
+    /// Room is reserved up front according to the size hint of `values`.
+    /// Like [`insert()`](#method.insert), no existing item is overwritten,
+    /// so the returned handles are distinct from each other and from the
+    /// handles already in the map.
+    ///
+    /// ### Example:
+    /// ```
+    /// use rand_map::RandMap;
+    ///
+    /// let mut map = RandMap::new();
+    /// let foo = map.insert("foo");
+    /// let handles = map.insert_many(vec!["bar", "baz"]);
+    /// assert_eq!(map.len(), 3);
+    /// assert!(!handles.contains(&foo));
+    /// assert_ne!(handles[0], handles[1]);
+    /// assert_eq!(map.get(handles[1]), Some(&"baz"));
+    /// ```
+    pub fn insert_many<I>(&mut self, values: I) -> Vec<Handle<V, K>>
+    where
+        I: IntoIterator<Item = V>,
+    {
+        let values = values.into_iter();
+        let (lower, _) = values.size_hint();
+        self.0.reserve(lower);
+        let mut handles = Vec::with_capacity(lower);
+        for value in values {
+            handles.push(insert_fresh(&mut self.0, value, &mut self.1));
+        }
+        handles
+    }
+}
+
+impl<V, R, K> Default for RandMap<V, R, K>
+where
+    R: Default,
+{
+    fn default() -> Self {
+        Self(HashMap::default(), R::default())
+    }
+}
+
+// 2^64 divided by the golden ratio, rounded to odd, the usual increment of
+// SplitMix64 and multiplier of Fibonacci hashing.
+pub(crate) const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+const UNSCRAMBLE: u64 = inverse(GOLDEN_GAMMA);
+
+// Spreads the bits of `key` over all bits, so that the pass-through hasher
+// can be used with keys that are not uniformly random, e.g. with some bits
+// fixed. Multiplying by an odd constant is a bijection.
+#[inline]
+pub(crate) fn scramble(key: u64) -> u64 {
+    key.wrapping_mul(GOLDEN_GAMMA)
+}
+
+// The inverse of `scramble()`.
+#[inline]
+pub(crate) fn unscramble(key: u64) -> u64 {
+    key.wrapping_mul(UNSCRAMBLE)
+}
+
+// The multiplicative inverse of odd `a` modulo 2^64, by Newton's method,
+// each step doubling the number of correct low bits.
+const fn inverse(a: u64) -> u64 {
+    let mut x = a;
+    let mut i = 0;
+    while i < 5 {
+        x = x.wrapping_mul(2u64.wrapping_sub(a.wrapping_mul(x)));
+        i += 1;
+    }
+    x
+}
+
+// Inserts `value` under a handle not yet in `map`, which may map the handles
+// to something other than the items.
+pub(crate) fn insert_fresh<V, T, R, K>(
+    map: &mut HashMap<Handle<V, K>, T, BuildHasherDefault<PassThroughHasher>>,
+    value: T,
+    rng: &mut R,
+) -> Handle<V, K>
+where
+    R: Rng + ?Sized,
+    K: HandleKey,
+{
+    assert!(
+        map.len() as u128 <= K::MAX,
+        "every possible handle is in use"
+    );
+    loop {
+        if let hash_map::Entry::Vacant(entry) = map.entry(rng.gen()) {
+            let key = *entry.key();
+            entry.insert(value);
+            return key;
+        }
+    }
 }
 
 /// The implementation uses [`iter()`(struct.RandMap.html#method.iter)
 ///
-impl<'a, V> IntoIterator for &'a RandMap<V> {
-    type Item = (Handle<V>, &'a V);
-    type IntoIter = Iter<'a, V>;
+impl<'a, V, R, K> IntoIterator for &'a RandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, &'a V);
+    type IntoIter = Iter<'a, V, K>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter()
     }
 }
 
-impl<V> PartialEq for RandMap<V>
+impl<'a, V, R, K> IntoIterator for &'a mut RandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, &'a mut V);
+    type IntoIter = IterMut<'a, V, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// Consumes the map, the iterator element type is `(Handle<V, K>, V)`.
+///
+impl<V, R, K> IntoIterator for RandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, V);
+    type IntoIter = IntoIter<V, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self.0.into_iter())
+    }
+}
+
+/// Like [`insert_key_value()`](struct.RandMap.html#method.insert_key_value),
+/// an existing item with the same handle is overwritten.
+impl<V, R, K> Extend<(Handle<V, K>, V)> for RandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    fn extend<I: IntoIterator<Item = (Handle<V, K>, V)>>(&mut self, iter: I) {
+        self.0.extend(iter)
+    }
+}
+
+/// The map gets `R::default()` as random source.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{Handle, RandMap};
+///
+/// let mut map: RandMap<&str> = vec![
+///     (Handle::from_u64(1), "one"),
+///     (Handle::from_u64(2), "two"),
+/// ]
+/// .into_iter()
+/// .collect();
+/// map.extend(vec![(Handle::from_u64(2), "TWO")]);
+/// assert_eq!(map.len(), 2);
+/// assert_eq!(map.get(Handle::from_u64(2)), Some(&"TWO"));
+/// let copy: RandMap<&str> = map.iter().map(|(k, v)| (k, *v)).collect();
+/// assert!(copy == map);
+/// ```
+impl<V, R, K> FromIterator<(Handle<V, K>, V)> for RandMap<V, R, K>
+where
+    R: Default,
+    K: HandleKey,
+{
+    fn from_iter<I: IntoIterator<Item = (Handle<V, K>, V)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect(), R::default())
+    }
+}
+
+/// Only the items are compared, not the random sources.
+impl<V, R, K> PartialEq for RandMap<V, R, K>
 where
     V: PartialEq,
+    K: HandleKey,
 {
-    fn eq(&self, other: &RandMap<V>) -> bool {
+    fn eq(&self, other: &RandMap<V, R, K>) -> bool {
         if self.len() != other.len() {
             return false;
         }
-        self.iter()
-            .all(|(key, val)| other.get(key).map_or(false, |v| *val == *v))
+        self.iter().all(|(key, val)| other.get(key) == Some(val))
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl<V, R, K> Serialize for RandMap<V, R, K>
+where
+    V: Serialize,
+    K: HandleKey + Serialize,
+{
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter())
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl<'de, V, R, K> Deserialize<'de> for RandMap<V, R, K>
+where
+    V: Deserialize<'de>,
+    R: Default,
+    K: HandleKey + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        Ok(Self(HashMap::deserialize(deserializer)?, R::default()))
+    }
+}
+
+/// A [`RandMap`](struct.RandMap.html) fit for storing capability-style
+/// tokens, e.g. session IDs or share links handed to untrusted clients.
+///
+/// The handles are 128 bit wide and drawn from the operating system's secure
+/// random source, so they are unguessable and may serve as the tokens
+/// themselves, without an extra lookup table.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{SecureHandle, SecureRandMap};
+///
+/// let mut sessions = SecureRandMap::default();
+/// let token = sessions.insert("alice");
+/// let cookie = token.to_string();
+/// assert!(cookie.len() <= 26);
+/// let token: SecureHandle<_> = cookie.parse().unwrap();
+/// assert_eq!(sessions.get(token), Some(&"alice"));
+/// ```
+pub type SecureRandMap<V> = RandMap<V, rand::rngs::OsRng, u128>;
+
+/// The handle of a [`SecureRandMap`](type.SecureRandMap.html).
+pub type SecureHandle<V> = Handle<V, u128>;
+
+/// The default random source of a [`RandMap`](struct.RandMap.html). Forwards
+/// to `rand::thread_rng()`, but unlike `rand::rngs::ThreadRng` it holds no
+/// state of its own.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultRng;
+
+impl RngCore for DefaultRng {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        rand::thread_rng().next_u32()
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        rand::thread_rng().next_u64()
+    }
+
+    #[inline]
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        rand::thread_rng().fill_bytes(dest)
+    }
+
+    #[inline]
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        rand::thread_rng().try_fill_bytes(dest)
     }
 }
 
 /// The type returned by [`RandMap::iter()`](struct.RandMap.html#method.iter).
 ///
-pub struct Iter<'a, V>(hash_map::Iter<'a, Handle<V>, V>);
-impl<'a, V> Iterator for Iter<'a, V> {
-    type Item = (Handle<V>, &'a V);
+pub struct Iter<'a, V, K = u64>(hash_map::Iter<'a, Handle<V, K>, V>);
+impl<'a, V, K> Iterator for Iter<'a, V, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, &'a V);
 
     fn next(&mut self) -> Option<Self::Item> {
         self.0.next().map(|(k, v)| (*k, v))
@@ -193,18 +796,99 @@ impl<'a, V> Iterator for Iter<'a, V> {
 /// The type returned by [`RandMap::iter_mut()`
 /// ](struct.RandMap.html#method.iter_mut).
 ///
-pub struct IterMut<'a, V>(hash_map::IterMut<'a, Handle<V>, V>);
-impl<'a, V> Iterator for IterMut<'a, V> {
-    type Item = (Handle<V>, &'a mut V);
+pub struct IterMut<'a, V, K = u64>(hash_map::IterMut<'a, Handle<V, K>, V>);
+impl<'a, V, K> Iterator for IterMut<'a, V, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, &'a mut V);
 
     fn next(&mut self) -> Option<Self::Item> {
         self.0.next().map(|(k, v)| (*k, v))
     }
 }
 
-/// The handle to a [`RandMap`](Struct.RandMap.html) item is a typed `u64`.
+/// The consuming iterator of a [`RandMap`](struct.RandMap.html).
+///
+pub struct IntoIter<V, K = u64>(hash_map::IntoIter<Handle<V, K>, V>);
+impl<V, K> Iterator for IntoIter<V, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+/// The type returned by [`RandMap::drain()`
+/// ](struct.RandMap.html#method.drain).
+///
+pub struct Drain<'a, V, K = u64>(hash_map::Drain<'a, Handle<V, K>, V>);
+impl<'a, V, K> Iterator for Drain<'a, V, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+/// The type returned by [`RandMap::extract_if()`
+/// ](struct.RandMap.html#method.extract_if).
+///
+pub struct ExtractIf<'a, V, F, K = u64>(
+    hash_map::ExtractIf<'a, Handle<V, K>, V, F>,
+)
+where
+    F: FnMut(&Handle<V, K>, &mut V) -> bool;
+impl<'a, V, F, K> Iterator for ExtractIf<'a, V, F, K>
+where
+    F: FnMut(&Handle<V, K>, &mut V) -> bool,
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+/// The handle to a [`RandMap`](Struct.RandMap.html) item is a typed `u64`,
+/// or a typed `u128` or whatever [`HandleKey`](trait.HandleKey.html) `K` is.
+///
+/// The type parameter is a mere marker, so a handle is `Send + Sync +
+/// 'static` whatever `V` is, and a `RandMap<V>` is `Send` and `Sync` as soon
+/// as `V` is.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{Handle, RandMap};
+/// use std::{cell::Cell, rc::Rc};
+///
+/// fn assert_send_sync<T: Send + Sync>() {}
+/// fn assert_static<T: 'static>() {}
+///
+/// assert_send_sync::<Handle<Rc<Cell<u8>>>>();
+/// assert_send_sync::<Handle<&str>>();
+/// assert_static::<Handle<Rc<Cell<u8>>>>();
+/// assert_send_sync::<RandMap<String>>();
+///
+/// let mut map = RandMap::new();
+/// let handle = map.insert("foo".to_string());
+/// let map = std::thread::spawn(move || map).join().unwrap();
+/// assert_eq!(map.get(handle).unwrap(), "foo");
+/// ```
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<rand_map::RandMap<std::rc::Rc<u8>>>();
+/// ```
 #[derive(Debug)]
-pub struct Handle<V>(u64, PhantomData<*const V>);
+pub struct Handle<V, K = u64>(K, PhantomData<fn() -> V>);
 
 impl<V> Handle<V> {
     #[inline]
@@ -218,15 +902,75 @@ impl<V> Handle<V> {
     }
 }
 
-impl<V> Clone for Handle<V> {
+impl<V> Handle<V, u32> {
+    #[inline]
+    pub fn from_u32(u: u32) -> Self {
+        Self(u, PhantomData)
+    }
+
+    #[inline]
+    pub fn as_u32(&self) -> u32 {
+        self.0
+    }
+}
+
+impl<V> Handle<V, u128> {
+    #[inline]
+    pub fn from_u128(u: u128) -> Self {
+        Self(u, PhantomData)
+    }
+
+    #[inline]
+    pub fn as_u128(&self) -> u128 {
+        self.0
+    }
+}
+
+impl<V, K> Handle<V, K>
+where
+    K: HandleKey,
+{
+    /// Creates a handle of any width from its raw representation.
+    #[inline]
+    pub fn from_raw(raw: K) -> Self {
+        Self(raw, PhantomData)
+    }
+
+    /// The raw representation of the handle.
+    #[inline]
+    pub fn as_raw(&self) -> K {
+        self.0
+    }
+
+    /// Decodes a string produced by [`encode()`](#method.encode).
+    #[inline]
+    pub fn decode(
+        s: &str,
+        encoding: HandleEncoding,
+    ) -> Result<Self, ParseHandleError> {
+        encoding.decode(s).map(Self::from_raw)
+    }
+
+    /// Encodes the handle as a string, see [`HandleEncoding`
+    /// ](enum.HandleEncoding.html).
+    #[inline]
+    pub fn encode(&self, encoding: HandleEncoding) -> String {
+        encoding.encode(self.0)
+    }
+}
+
+impl<V, K> Clone for Handle<V, K>
+where
+    K: Clone,
+{
     fn clone(&self) -> Self {
-        *self
+        Self(self.0.clone(), PhantomData)
     }
 }
 
-impl<V> Copy for Handle<V> { }
+impl<V, K> Copy for Handle<V, K> where K: Copy {}
 
-impl<V> Eq for Handle<V> {}
+impl<V, K> Eq for Handle<V, K> where K: HandleKey {}
 
 impl<V> From<u64> for Handle<V> {
     fn from(item: u64) -> Handle<V> {
@@ -240,34 +984,99 @@ impl<V> From<Handle<V>> for u64 {
     }
 }
 
-impl<V> Hash for Handle<V> {
+impl<V> From<u32> for Handle<V, u32> {
+    fn from(item: u32) -> Handle<V, u32> {
+        Self(item, PhantomData)
+    }
+}
+
+impl<V> From<Handle<V, u32>> for u32 {
+    fn from(item: Handle<V, u32>) -> u32 {
+        item.as_u32()
+    }
+}
+
+impl<V> From<u128> for Handle<V, u128> {
+    fn from(item: u128) -> Handle<V, u128> {
+        Self(item, PhantomData)
+    }
+}
+
+impl<V> From<Handle<V, u128>> for u128 {
+    fn from(item: Handle<V, u128>) -> u128 {
+        item.as_u128()
+    }
+}
+
+impl<V, K> Hash for Handle<V, K>
+where
+    K: HandleKey,
+{
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.0.hash(state);
+        state.write_u64(self.0.hash_bits());
     }
 }
 
-impl<V> Ord for Handle<V> {
+impl<V, K> Ord for Handle<V, K>
+where
+    K: HandleKey,
+{
     fn cmp(&self, other: &Self) -> Ordering {
         self.0.cmp(&other.0)
     }
 }
 
-impl<V> PartialEq for Handle<V> {
+impl<V, K> PartialEq for Handle<V, K>
+where
+    K: HandleKey,
+{
     fn eq(&self, other: &Self) -> bool {
         self.0 == other.0
     }
 }
 
-impl<V> PartialOrd for Handle<V> {
+impl<V, K> PartialOrd for Handle<V, K>
+where
+    K: HandleKey,
+{
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl<V> rand::distributions::Distribution<Handle<V>>
-for rand::distributions::Standard {
-    fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Handle<V> {
-        Handle(rng.gen(), PhantomData)
+/// Serialized as the plain `u64` (or whatever `K` is).
+#[cfg(feature = "serialize")]
+impl<V, K> Serialize for Handle<V, K>
+where
+    K: HandleKey + Serialize,
+{
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl<'de, V, K> Deserialize<'de> for Handle<V, K>
+where
+    K: HandleKey + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        K::deserialize(deserializer).map(Handle::from_raw)
+    }
+}
+
+impl<V, K> rand::distributions::Distribution<Handle<V, K>>
+for rand::distributions::Standard
+where
+    K: HandleKey,
+{
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Handle<V, K> {
+        Handle(K::random(rng), PhantomData)
     }
 }
 
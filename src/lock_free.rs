@@ -0,0 +1,355 @@
+//! A map with lock-free lookups, see [`LockFreeRandMap`
+//! ](../struct.LockFreeRandMap.html).
+
+// Every `unsafe` block below must say why it is sound.
+#![deny(clippy::undocumented_unsafe_blocks)]
+
+use crate::Handle;
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+use rand::Rng;
+use std::fmt;
+use std::iter::FromIterator;
+use std::sync::atomic::Ordering::{AcqRel, Acquire, Release};
+use std::sync::{Mutex, MutexGuard};
+
+const MIN_CAPACITY: usize = 8;
+const TOMBSTONE: usize = 1;
+
+/// A concurrent map whose lookups never take a lock. Requires the
+/// `lock_free` feature.
+///
+/// The items live in an open-addressed table indexed directly by the low
+/// bits of their random handles. Readers probe the table without locking,
+/// while writers are serialized by a mutex. Removed items and outgrown
+/// tables are reclaimed only when no reader can see them any longer, using
+/// [`crossbeam-epoch`](https://docs.rs/crossbeam-epoch).
+///
+/// As a reader may be looking at an item while it is being removed, items
+/// are never moved out of the map. Hence [`remove()`](#method.remove) does
+/// not return the removed value.
+///
+/// The handles are the same as those of a [`RandMap`
+/// ](struct.RandMap.html), so handles may be passed between the two.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{LockFreeRandMap, RandMap};
+/// use std::{sync::Arc, thread};
+///
+/// let mut map = RandMap::new();
+/// let foo = map.insert("foo".to_string());
+/// let lock_free: LockFreeRandMap<_> = map.into_iter().collect();
+/// let lock_free = Arc::new(lock_free);
+/// let bar = lock_free.insert("bar".to_string());
+/// let reader = {
+///     let lock_free = Arc::clone(&lock_free);
+///     thread::spawn(move || lock_free.get_with(foo, |v| v.len()))
+/// };
+/// assert_eq!(reader.join().unwrap(), Some(3));
+/// assert_eq!(lock_free.get(bar).unwrap(), "bar");
+/// assert!(lock_free.remove(foo));
+/// assert!(!lock_free.contains(foo));
+/// assert_eq!(lock_free.len(), 1);
+/// ```
+pub struct LockFreeRandMap<V> {
+    table: Atomic<Table<V>>,
+    writer: Mutex<Counts>,
+}
+
+struct Counts {
+    len: usize,
+    // Live items plus tombstones.
+    used: usize,
+}
+
+struct Table<V> {
+    slots: Box<[Atomic<Item<V>>]>,
+}
+
+struct Item<V> {
+    key: Handle<V>,
+    value: V,
+}
+
+impl<V> LockFreeRandMap<V>
+where
+    V: Send + 'static,
+{
+    /// Creates an empty map.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            table: Atomic::new(Table::with_capacity(MIN_CAPACITY)),
+            writer: Mutex::new(Counts { len: 0, used: 0 }),
+        }
+    }
+
+    /// Whether `handle` refers to an item in the map.
+    #[inline]
+    pub fn contains(&self, handle: Handle<V>) -> bool {
+        self.get_with(handle, |_| ()).is_some()
+    }
+
+    /// Calls `f` with a reference to the `V` of `handle`, and returns its
+    /// result, or `None` if not found. Never blocks.
+    pub fn get_with<F, T>(&self, handle: Handle<V>, f: F) -> Option<T>
+    where
+        F: FnOnce(&V) -> T,
+    {
+        let guard = epoch::pin();
+        // SAFETY: The table pointer is never null, and a table is only
+        // destroyed through `defer_destroy()` once swapped out by `grow()`,
+        // so it outlives `guard`, which was pinned before the load.
+        let table = unsafe { self.table.load(Acquire, &guard).deref() };
+        table.find(handle, &guard).map(|(_, item)| {
+            // SAFETY: `find()` returns a non-null item loaded under `guard`.
+            // Writers unlink an item under the writer mutex before passing
+            // it to `defer_destroy()`, so it is freed only once `guard` is
+            // dropped, even if it is removed or replaced meanwhile.
+            f(unsafe { &item.deref().value })
+        })
+    }
+
+    /// Insert a `V` and get a handle for retrieval. Like [`RandMap::insert()`
+    /// ](struct.RandMap.html#method.insert), no existing item is ever
+    /// overwritten.
+    pub fn insert(&self, value: V) -> Handle<V> {
+        let mut counts = self.lock();
+        let guard = epoch::pin();
+        let mut rng = rand::thread_rng();
+        let key = loop {
+            let key = rng.gen();
+            // SAFETY: The table is not null, and as we hold the writer
+            // mutex, no one can swap it out and destroy it.
+            let table = unsafe { self.table.load(Acquire, &guard).deref() };
+            if table.find(key, &guard).is_none() {
+                break key;
+            }
+        };
+        self.insert_new(&mut counts, key, value, &guard);
+        key
+    }
+
+    /// Insert a key-value pair. Returns `true` if an item with handle `key`
+    /// was replaced.
+    pub fn insert_key_value(&self, key: Handle<V>, value: V) -> bool {
+        let mut counts = self.lock();
+        let guard = epoch::pin();
+        // SAFETY: The table is not null, and as we hold the writer mutex, no
+        // one can swap it out and destroy it.
+        let table = unsafe { self.table.load(Acquire, &guard).deref() };
+        match table.find(key, &guard) {
+            Some((index, _)) => {
+                let old = table.slots[index].swap(
+                    Owned::new(Item { key, value }),
+                    AcqRel,
+                    &guard,
+                );
+                // SAFETY: `old` was just unlinked from the current table
+                // under the writer mutex, so no later reader can load it. An
+                // outgrown table may still point to it, but such a table is
+                // only reachable by readers pinned before its own
+                // `defer_destroy()`, so before this one, too.
+                unsafe { guard.defer_destroy(old) };
+                true
+            }
+            None => {
+                self.insert_new(&mut counts, key, value, &guard);
+                false
+            }
+        }
+    }
+
+    /// Whether the map is empty. Under concurrent modification this is but
+    /// a snapshot.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of items. Waits for any ongoing insertion or removal.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.lock().len
+    }
+
+    /// Remove the item with handle `handle`. Returns `false` if not found.
+    pub fn remove(&self, handle: Handle<V>) -> bool {
+        let mut counts = self.lock();
+        let guard = epoch::pin();
+        // SAFETY: The table is not null, and as we hold the writer mutex, no
+        // one can swap it out and destroy it.
+        let table = unsafe { self.table.load(Acquire, &guard).deref() };
+        match table.find(handle, &guard) {
+            Some((index, _)) => {
+                let old = table.slots[index].swap(
+                    Shared::null().with_tag(TOMBSTONE),
+                    AcqRel,
+                    &guard,
+                );
+                // SAFETY: As in `insert_key_value()`, `old` was unlinked
+                // under the writer mutex, and readers that may still see it
+                // are pinned, so it is freed only after they unpin.
+                unsafe { guard.defer_destroy(old) };
+                counts.len -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert_new(
+        &self,
+        counts: &mut Counts,
+        key: Handle<V>,
+        value: V,
+        guard: &Guard,
+    ) {
+        // SAFETY: The caller holds the writer mutex, as witnessed by
+        // `counts`, so the table is not null and cannot be destroyed.
+        let mut table = unsafe { self.table.load(Acquire, guard).deref() };
+        if (counts.used + 1) * 4 > table.slots.len() * 3 {
+            let capacity =
+                ((counts.len + 1) * 2).next_power_of_two().max(MIN_CAPACITY);
+            let grown = Table::with_capacity(capacity);
+            for slot in table.slots.iter() {
+                let item = slot.load(Acquire, guard);
+                if !item.is_null() {
+                    // SAFETY: Items are only unlinked and destroyed under the
+                    // writer mutex, which we hold.
+                    let key = unsafe { item.deref().key };
+                    grown.slots[grown.vacant(key, guard)]
+                        .store(item, Release);
+                }
+            }
+            let old = self.table.swap(Owned::new(grown), AcqRel, guard);
+            // SAFETY: `old` is unlinked, and readers that loaded it before
+            // are pinned, so it is freed only after they unpin. Dropping a
+            // `Table` frees its slots, not the items, which the grown table
+            // holds now, so a reader's item from `old` stays valid, too.
+            unsafe { guard.defer_destroy(old) };
+            counts.used = counts.len;
+            // SAFETY: We just stored the grown table, and hold the writer
+            // mutex, so it is not null and cannot be destroyed.
+            table = unsafe { self.table.load(Acquire, guard).deref() };
+        }
+        let slot = &table.slots[table.vacant(key, guard)];
+        if slot.load(Acquire, guard).tag() != TOMBSTONE {
+            counts.used += 1;
+        }
+        slot.store(Owned::new(Item { key, value }), Release);
+        counts.len += 1;
+    }
+
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, Counts> {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<V> LockFreeRandMap<V>
+where
+    V: Clone + Send + 'static,
+{
+    /// Retrieves a clone of the `V` of `handle`. Never blocks.
+    #[inline]
+    pub fn get(&self, handle: Handle<V>) -> Option<V> {
+        self.get_with(handle, V::clone)
+    }
+}
+
+impl<V> Table<V> {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity).map(|_| Atomic::null()).collect(),
+        }
+    }
+
+    // The slot holding `key` and its item. A reader must use the returned
+    // item rather than reload the slot, which may have been emptied since.
+    fn find<'g>(
+        &self,
+        key: Handle<V>,
+        guard: &'g Guard,
+    ) -> Option<(usize, Shared<'g, Item<V>>)> {
+        for index in self.probe(key) {
+            let item = self.slots[index].load(Acquire, guard);
+            if item.is_null() {
+                if item.tag() == TOMBSTONE {
+                    continue;
+                }
+                return None;
+            }
+            // SAFETY: The item is not null, and was loaded under `guard`.
+            // Items are only destroyed through `defer_destroy()` after being
+            // unlinked, so it outlives `guard`.
+            if unsafe { item.deref() }.key == key {
+                return Some((index, item));
+            }
+        }
+        None
+    }
+
+    #[inline]
+    fn probe(&self, key: Handle<V>) -> impl Iterator<Item = usize> {
+        let mask = self.slots.len() - 1;
+        let start = key.as_u64() as usize;
+        (0..self.slots.len()).map(move |i| start.wrapping_add(i) & mask)
+    }
+
+    // The index of the first empty slot or tombstone for `key`, which must
+    // not be in the table.
+    fn vacant(&self, key: Handle<V>, guard: &Guard) -> usize {
+        self.probe(key)
+            .find(|&index| self.slots[index].load(Acquire, guard).is_null())
+            .expect("table full")
+    }
+}
+
+impl<V> Default for LockFreeRandMap<V>
+where
+    V: Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> Drop for LockFreeRandMap<V> {
+    fn drop(&mut self) {
+        // SAFETY: `&mut self` means no other thread can access the map, so
+        // no guard is needed. The table is not null, and each item is in
+        // exactly one slot of it, so each is freed exactly once. Items and
+        // tables unlinked earlier are freed by their `defer_destroy()`.
+        unsafe {
+            let guard = epoch::unprotected();
+            let table = self.table.load(Acquire, guard);
+            for slot in table.deref().slots.iter() {
+                let item = slot.load(Acquire, guard);
+                if !item.is_null() {
+                    drop(item.into_owned());
+                }
+            }
+            drop(table.into_owned());
+        }
+    }
+}
+
+impl<V> fmt::Debug for LockFreeRandMap<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LockFreeRandMap").finish_non_exhaustive()
+    }
+}
+
+impl<V> FromIterator<(Handle<V>, V)> for LockFreeRandMap<V>
+where
+    V: Send + 'static,
+{
+    fn from_iter<I: IntoIterator<Item = (Handle<V>, V)>>(iter: I) -> Self {
+        let map = Self::new();
+        for (key, value) in iter {
+            map.insert_key_value(key, value);
+        }
+        map
+    }
+}
@@ -0,0 +1,478 @@
+//! A map ordered by handle, see [`BTreeRandMap`
+//! ](../struct.BTreeRandMap.html).
+
+use crate::{DefaultRng, Handle, HandleKey, RandMap};
+use rand::{Rng, RngCore};
+use std::collections::btree_map::{self, BTreeMap};
+use std::iter::FromIterator;
+use std::ops::RangeBounds;
+
+/// A [`RandMap`](struct.RandMap.html) look-alike keeping its items in a
+/// `BTreeMap`, ordered by handle.
+///
+/// Iteration yields the items in ascending order of their handles, and
+/// [`range()`](#method.range) yields those in a range of handles. This
+/// allows paging through a map deterministically, or partitioning work by
+/// handle ranges, which, handles being random, are about evenly filled.
+/// The price is that lookups take logarithmic rather than constant time.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{BTreeRandMap, Handle};
+/// use std::ops::Bound::{Excluded, Unbounded};
+///
+/// let mut map = BTreeRandMap::new();
+/// for i in 0..10 {
+///     map.insert(i);
+/// }
+/// let (first, _) = map.first().unwrap();
+/// let (last, _) = map.last().unwrap();
+/// assert!(first < last);
+///
+/// // Page through the map, three items at a time.
+/// let mut pages = Vec::new();
+/// let mut from = Unbounded;
+/// loop {
+///     let page: Vec<_> = map.range((from, Unbounded)).take(3).collect();
+///     match page.last() {
+///         Some(&(handle, _)) => from = Excluded(handle),
+///         None => break,
+///     }
+///     pages.push(page);
+/// }
+/// assert_eq!(pages.len(), 4);
+/// assert_eq!(pages.concat(), map.iter().collect::<Vec<_>>());
+///
+/// // Split the handles in halves.
+/// let middle = Handle::from_u64(1 << 63);
+/// let low = map.range(..middle).count();
+/// assert_eq!(low + map.range(middle..).count(), 10);
+/// ```
+#[derive(Clone, Debug)]
+pub struct BTreeRandMap<V, R = DefaultRng, K = u64> {
+    items: BTreeMap<Handle<V, K>, V>,
+    rng: R,
+}
+
+impl<V> BTreeRandMap<V> {
+    /// Creates an empty map.
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_rng(DefaultRng)
+    }
+}
+
+impl<V, R> BTreeRandMap<V, R> {
+    /// Creates an empty map that draws its handles from `rng`.
+    #[inline]
+    pub fn with_rng(rng: R) -> Self {
+        Self::from_rng(rng)
+    }
+}
+
+impl<V, R, K> BTreeRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    /// Like [`with_rng()`](#method.with_rng), but for any handle width `K`.
+    #[inline]
+    pub fn from_rng(rng: R) -> Self {
+        Self {
+            items: BTreeMap::new(),
+            rng,
+        }
+    }
+
+    #[inline]
+    pub fn rng(&self) -> &R {
+        &self.rng
+    }
+
+    #[inline]
+    pub fn rng_mut(&mut self) -> &mut R {
+        &mut self.rng
+    }
+
+    /// Borrow the contained `BTreeMap`.
+    #[inline]
+    pub fn as_btree_map(&self) -> &BTreeMap<Handle<V, K>, V> {
+        &self.items
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// The item with the lowest handle.
+    #[inline]
+    pub fn first(&self) -> Option<(Handle<V, K>, &V)> {
+        self.items.iter().next().map(|(k, v)| (*k, v))
+    }
+
+    #[inline]
+    pub fn get(&self, handle: Handle<V, K>) -> Option<&V> {
+        self.items.get(&handle)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, handle: Handle<V, K>) -> Option<&mut V> {
+        self.items.get_mut(&handle)
+    }
+
+    /// Like [`insert()`](#method.insert), but draws the handle from `rng`
+    /// rather than from the map's own random source.
+    pub fn insert_with_rng<G>(
+        &mut self,
+        value: V,
+        rng: &mut G,
+    ) -> Handle<V, K>
+    where
+        G: Rng + ?Sized,
+    {
+        insert_fresh(&mut self.items, value, rng)
+    }
+
+    /// Insert a key-value pair, overwriting any existing item with handle
+    /// `key`.
+    #[inline]
+    pub fn insert_key_value(&mut self, key: Handle<V, K>, value: V) {
+        self.items.insert(key, value);
+    }
+
+    /// Insert a key-value pair and return the value previously stored under
+    /// `key`, if any.
+    #[inline]
+    pub fn replace_key_value(
+        &mut self,
+        key: Handle<V, K>,
+        value: V,
+    ) -> Option<V> {
+        self.items.insert(key, value)
+    }
+
+    /// Insert a key-value pair unless `key` is already present, in which
+    /// case the map is left untouched and `value` is given back.
+    pub fn try_insert_key_value(
+        &mut self,
+        key: Handle<V, K>,
+        value: V,
+    ) -> Result<(), V> {
+        match self.items.entry(key) {
+            btree_map::Entry::Occupied(_) => Err(value),
+            btree_map::Entry::Vacant(entry) => {
+                entry.insert(value);
+                Ok(())
+            }
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The iterator element type is `(Handle<V, K>, &V)`, in ascending
+    /// order of the handles.
+    #[inline]
+    pub fn iter(&self) -> BTreeIter<'_, V, K> {
+        BTreeIter(self.items.iter())
+    }
+
+    /// The iterator element type is `(Handle<V, K>, &mut V)`, in ascending
+    /// order of the handles.
+    #[inline]
+    pub fn iter_mut(&mut self) -> BTreeIterMut<'_, V, K> {
+        BTreeIterMut(self.items.iter_mut())
+    }
+
+    /// The item with the highest handle.
+    #[inline]
+    pub fn last(&self) -> Option<(Handle<V, K>, &V)> {
+        self.items.iter().next_back().map(|(k, v)| (*k, v))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Remove and return the item with the lowest handle.
+    #[inline]
+    pub fn pop_first(&mut self) -> Option<(Handle<V, K>, V)> {
+        self.items.pop_first()
+    }
+
+    /// Remove and return the item with the highest handle.
+    #[inline]
+    pub fn pop_last(&mut self) -> Option<(Handle<V, K>, V)> {
+        self.items.pop_last()
+    }
+
+    /// The items with handles in `range`, in ascending order of the handles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range starts after it ends, or starts and ends at the
+    /// same handle, both excluded.
+    #[inline]
+    pub fn range<B>(&self, range: B) -> BTreeRange<'_, V, K>
+    where
+        B: RangeBounds<Handle<V, K>>,
+    {
+        BTreeRange(self.items.range(range))
+    }
+
+    /// The items with handles in `range`, see [`range()`](#method.range).
+    #[inline]
+    pub fn range_mut<B>(&mut self, range: B) -> BTreeRangeMut<'_, V, K>
+    where
+        B: RangeBounds<Handle<V, K>>,
+    {
+        BTreeRangeMut(self.items.range_mut(range))
+    }
+
+    /// Remove and return the value with handle `handle`, or `None` if not
+    /// found.
+    #[inline]
+    pub fn remove(&mut self, handle: Handle<V, K>) -> Option<V> {
+        self.items.remove(&handle)
+    }
+
+    /// Retains only the items for which `f` returns `true`.
+    #[inline]
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Handle<V, K>, &mut V) -> bool,
+    {
+        self.items.retain(|k, v| f(*k, v))
+    }
+
+    /// Moves the items with handles from `at` on into a new map, which gets
+    /// a clone of the random source.
+    #[inline]
+    pub fn split_off(&mut self, at: Handle<V, K>) -> Self
+    where
+        R: Clone,
+    {
+        Self {
+            items: self.items.split_off(&at),
+            rng: self.rng.clone(),
+        }
+    }
+}
+
+impl<V, R, K> BTreeRandMap<V, R, K>
+where
+    R: RngCore,
+    K: HandleKey,
+{
+    /// Insert a `V` and get a handle for retrieval, see [`RandMap::insert()`
+    /// ](struct.RandMap.html#method.insert).
+    #[inline]
+    pub fn insert(&mut self, value: V) -> Handle<V, K> {
+        insert_fresh(&mut self.items, value, &mut self.rng)
+    }
+}
+
+impl<V, R, K> Default for BTreeRandMap<V, R, K>
+where
+    R: Default,
+    K: HandleKey,
+{
+    fn default() -> Self {
+        Self::from_rng(R::default())
+    }
+}
+
+impl<'a, V, R, K> IntoIterator for &'a BTreeRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, &'a V);
+    type IntoIter = BTreeIter<'a, V, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, V, R, K> IntoIterator for &'a mut BTreeRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, &'a mut V);
+    type IntoIter = BTreeIterMut<'a, V, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// Consumes the map, the iterator element type is `(Handle<V, K>, V)`, in
+/// ascending order of the handles.
+///
+impl<V, R, K> IntoIterator for BTreeRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, V);
+    type IntoIter = BTreeIntoIter<V, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        BTreeIntoIter(self.items.into_iter())
+    }
+}
+
+/// Like [`insert_key_value()`
+/// ](struct.BTreeRandMap.html#method.insert_key_value), an existing item
+/// with the same handle is overwritten.
+impl<V, R, K> Extend<(Handle<V, K>, V)> for BTreeRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    fn extend<I: IntoIterator<Item = (Handle<V, K>, V)>>(&mut self, iter: I) {
+        self.items.extend(iter)
+    }
+}
+
+/// The map gets `R::default()` as random source.
+impl<V, R, K> FromIterator<(Handle<V, K>, V)> for BTreeRandMap<V, R, K>
+where
+    R: Default,
+    K: HandleKey,
+{
+    fn from_iter<I: IntoIterator<Item = (Handle<V, K>, V)>>(iter: I) -> Self {
+        Self {
+            items: iter.into_iter().collect(),
+            rng: R::default(),
+        }
+    }
+}
+
+/// Keeps the handles and the random source.
+impl<V, R, K> From<RandMap<V, R, K>> for BTreeRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    fn from(map: RandMap<V, R, K>) -> Self {
+        let RandMap(items, rng) = map;
+        Self {
+            items: items.into_iter().collect(),
+            rng,
+        }
+    }
+}
+
+/// Keeps the handles and the random source.
+impl<V, R, K> From<BTreeRandMap<V, R, K>> for RandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    fn from(map: BTreeRandMap<V, R, K>) -> Self {
+        RandMap(map.items.into_iter().collect(), map.rng)
+    }
+}
+
+/// Only the items are compared, not the random sources.
+impl<V, R, K> PartialEq for BTreeRandMap<V, R, K>
+where
+    V: PartialEq,
+    K: HandleKey,
+{
+    fn eq(&self, other: &BTreeRandMap<V, R, K>) -> bool {
+        self.items == other.items
+    }
+}
+
+macro_rules! btree_iter {
+    ($(#[$doc:meta])* $name:ident<$($a:lifetime,)? V, K>, $inner:ty,
+     $item:ty, $map:expr) => {
+        $(#[$doc])*
+        ///
+        pub struct $name<$($a,)? V, K = u64>($inner);
+        impl<$($a,)? V, K> Iterator for $name<$($a,)? V, K>
+        where
+            K: HandleKey,
+        {
+            type Item = $item;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                self.0.next().map($map)
+            }
+        }
+
+        impl<$($a,)? V, K> DoubleEndedIterator for $name<$($a,)? V, K>
+        where
+            K: HandleKey,
+        {
+            fn next_back(&mut self) -> Option<Self::Item> {
+                self.0.next_back().map($map)
+            }
+        }
+    };
+}
+
+btree_iter!(
+    /// The type returned by [`BTreeRandMap::iter()`
+    /// ](struct.BTreeRandMap.html#method.iter).
+    BTreeIter<'a, V, K>,
+    btree_map::Iter<'a, Handle<V, K>, V>,
+    (Handle<V, K>, &'a V),
+    |(k, v)| (*k, v)
+);
+btree_iter!(
+    /// The type returned by [`BTreeRandMap::iter_mut()`
+    /// ](struct.BTreeRandMap.html#method.iter_mut).
+    BTreeIterMut<'a, V, K>,
+    btree_map::IterMut<'a, Handle<V, K>, V>,
+    (Handle<V, K>, &'a mut V),
+    |(k, v)| (*k, v)
+);
+btree_iter!(
+    /// The consuming iterator of a [`BTreeRandMap`
+    /// ](struct.BTreeRandMap.html).
+    BTreeIntoIter<V, K>,
+    btree_map::IntoIter<Handle<V, K>, V>,
+    (Handle<V, K>, V),
+    |item| item
+);
+btree_iter!(
+    /// The type returned by [`BTreeRandMap::range()`
+    /// ](struct.BTreeRandMap.html#method.range).
+    BTreeRange<'a, V, K>,
+    btree_map::Range<'a, Handle<V, K>, V>,
+    (Handle<V, K>, &'a V),
+    |(k, v)| (*k, v)
+);
+btree_iter!(
+    /// The type returned by [`BTreeRandMap::range_mut()`
+    /// ](struct.BTreeRandMap.html#method.range_mut).
+    BTreeRangeMut<'a, V, K>,
+    btree_map::RangeMut<'a, Handle<V, K>, V>,
+    (Handle<V, K>, &'a mut V),
+    |(k, v)| (*k, v)
+);
+
+// Like `crate::insert_fresh()`, for the ordered items.
+fn insert_fresh<V, R, K>(
+    items: &mut BTreeMap<Handle<V, K>, V>,
+    value: V,
+    rng: &mut R,
+) -> Handle<V, K>
+where
+    R: Rng + ?Sized,
+    K: HandleKey,
+{
+    assert!(
+        items.len() as u128 <= K::MAX,
+        "every possible handle is in use"
+    );
+    loop {
+        if let btree_map::Entry::Vacant(entry) = items.entry(rng.gen()) {
+            let key = *entry.key();
+            entry.insert(value);
+            return key;
+        }
+    }
+}
@@ -0,0 +1,430 @@
+//! A map with dense storage and obfuscated handles, see
+//! [`PermutedRandMap`](../struct.PermutedRandMap.html).
+
+use crate::{Handle, GOLDEN_GAMMA};
+use rand::Rng;
+use std::fmt;
+use std::iter::Enumerate;
+use std::{slice, vec};
+
+const ROUNDS: usize = 8;
+
+/// A map storing its items in a dense vector, whose handles nevertheless
+/// look random.
+///
+/// Internally, an item is identified by its index in the vector and a
+/// generation counter, which is bumped whenever the slot is freed. The
+/// handle given out is this pair sent through a keyed 64 bit bijection, a
+/// Feistel network, so handles are unpredictable without the key, and a
+/// guessed handle almost certainly refers to nothing.
+///
+/// Freed slots are reused, which keeps the vector compact. As the generation
+/// counter wraps after 2^32 reuses of a slot, a handle that is that much out
+/// of date may erroneously refer to a newer item.
+///
+/// The permutation is an obfuscation, not a cryptographic guarantee. To
+/// reject forged handles, see `SignedRandMap` (feature `signed`). Maps
+/// sharing a key, e.g. in several processes, map the same slots to the same
+/// handles, see [`with_key()`](#method.with_key).
+///
+/// ### Example:
+/// ```
+/// use rand_map::{Handle, PermutedRandMap};
+///
+/// let mut map = PermutedRandMap::with_key(4711);
+/// let foo = map.insert("foo");
+/// let bar = map.insert("bar");
+/// assert_eq!(map.get(foo), Some(&"foo"));
+/// assert_ne!(bar.as_u64(), foo.as_u64() + 1);
+/// assert_eq!(map.remove(foo), Some("foo"));
+/// // The slot of `foo` is reused, but `foo` stays invalid.
+/// let baz = map.insert("baz");
+/// assert_ne!(baz, foo);
+/// assert_eq!(map.get(foo), None);
+/// assert_eq!(map.get(baz), Some(&"baz"));
+/// assert_eq!(map.get(Handle::from_u64(1)), None);
+///
+/// let same_key = PermutedRandMap::<&str>::with_key(4711);
+/// assert_eq!(same_key.key(), map.key());
+/// ```
+#[derive(Clone)]
+pub struct PermutedRandMap<V> {
+    slots: Vec<Slot<V>>,
+    free: Vec<u32>,
+    len: usize,
+    key: u64,
+    round_keys: [u64; ROUNDS],
+}
+
+#[derive(Clone, Debug)]
+struct Slot<V> {
+    generation: u32,
+    value: Option<V>,
+}
+
+impl<V> PermutedRandMap<V> {
+    /// Creates an empty map with a random key.
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_key(rand::thread_rng().gen())
+    }
+
+    /// Creates an empty map that obfuscates its handles using `key`.
+    pub fn with_key(key: u64) -> Self {
+        let mut state = key;
+        let mut round_keys = [0; ROUNDS];
+        for round_key in round_keys.iter_mut() {
+            state = state.wrapping_add(GOLDEN_GAMMA);
+            *round_key = mix(state);
+        }
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+            key,
+            round_keys,
+        }
+    }
+
+    /// The key the handles are obfuscated with.
+    #[inline]
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    /// Clears the map. Like after [`remove()`](#method.remove), the handles
+    /// of the items refer to nothing, even once their slots are reused.
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            if slot.value.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+            }
+        }
+        self.free.clear();
+        self.free.extend((0..self.slots.len() as u32).rev());
+        self.len = 0;
+    }
+
+    /// Clears the map, returning all handle-value pairs as an iterator, in
+    /// the order of the slots. Like after [`remove()`](#method.remove), the
+    /// handles refer to nothing afterwards.
+    ///
+    /// If the returned iterator is dropped before being fully consumed, it
+    /// drops the remaining pairs.
+    #[inline]
+    pub fn drain(&mut self) -> PermutedDrain<'_, V> {
+        PermutedDrain {
+            map: self,
+            index: 0,
+        }
+    }
+
+    /// Whether `handle` refers to an item in the map.
+    #[inline]
+    pub fn contains(&self, handle: Handle<V>) -> bool {
+        self.get(handle).is_some()
+    }
+
+    /// Retrieves a reference to a `V`.
+    #[inline]
+    pub fn get(&self, handle: Handle<V>) -> Option<&V> {
+        let (index, generation) = self.decode(handle);
+        match self.slots.get(index)? {
+            Slot {
+                generation: g,
+                value: Some(value),
+            } if *g == generation => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Retrieves a mutable reference to a `V`.
+    #[inline]
+    pub fn get_mut(&mut self, handle: Handle<V>) -> Option<&mut V> {
+        let (index, generation) = self.decode(handle);
+        match self.slots.get_mut(index)? {
+            Slot {
+                generation: g,
+                value: Some(value),
+            } if *g == generation => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Insert a `V` and get a handle for retrieval.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map already holds 2^32 items.
+    pub fn insert(&mut self, value: V) -> Handle<V> {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                let index = self.slots.len();
+                assert!(index <= u32::MAX as usize, "every slot is in use");
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: None,
+                });
+                index as u32
+            }
+        };
+        let slot = &mut self.slots[index as usize];
+        slot.value = Some(value);
+        let generation = slot.generation;
+        self.len += 1;
+        encode(&self.round_keys, index, generation)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The iterator element type is `(Handle<V>, &V)`, in the order of the
+    /// slots.
+    #[inline]
+    pub fn iter(&self) -> PermutedIter<'_, V> {
+        PermutedIter {
+            round_keys: &self.round_keys,
+            slots: self.slots.iter().enumerate(),
+        }
+    }
+
+    /// The iterator element type is `(Handle<V>, &mut V)`, in the order of
+    /// the slots.
+    #[inline]
+    pub fn iter_mut(&mut self) -> PermutedIterMut<'_, V> {
+        PermutedIterMut {
+            round_keys: &self.round_keys,
+            slots: self.slots.iter_mut().enumerate(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Remove and return the value with handle `handle`, or `None` if not
+    /// found. Afterwards, `handle` refers to nothing, even once its slot is
+    /// reused.
+    pub fn remove(&mut self, handle: Handle<V>) -> Option<V> {
+        let (index, generation) = self.decode(handle);
+        if self.slots.get(index)?.generation != generation {
+            return None;
+        }
+        self.take(index)
+    }
+
+    /// Retains only the items for which `f` returns `true`. The handles of
+    /// the others refer to nothing afterwards.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Handle<V>, &mut V) -> bool,
+    {
+        for index in 0..self.slots.len() {
+            let slot = &mut self.slots[index];
+            if let Some(value) = &mut slot.value {
+                let handle =
+                    encode(&self.round_keys, index as u32, slot.generation);
+                if !f(handle, value) {
+                    self.take(index);
+                }
+            }
+        }
+    }
+
+    fn decode(&self, handle: Handle<V>) -> (usize, u32) {
+        let u = handle.as_u64();
+        let (mut left, mut right) = ((u >> 32) as u32, u as u32);
+        for &round_key in self.round_keys.iter().rev() {
+            let f = round(left, round_key);
+            right = std::mem::replace(&mut left, right ^ f);
+        }
+        (right as usize, left)
+    }
+
+    // Takes the item out of slot `index`, if any, and frees the slot under
+    // the next generation.
+    fn take(&mut self, index: usize) -> Option<V> {
+        let slot = &mut self.slots[index];
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(index as u32);
+        self.len -= 1;
+        Some(value)
+    }
+}
+
+impl<V> Default for PermutedRandMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, V> IntoIterator for &'a PermutedRandMap<V> {
+    type Item = (Handle<V>, &'a V);
+    type IntoIter = PermutedIter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, V> IntoIterator for &'a mut PermutedRandMap<V> {
+    type Item = (Handle<V>, &'a mut V);
+    type IntoIter = PermutedIterMut<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// Consumes the map, the iterator element type is `(Handle<V>, V)`, in the
+/// order of the slots.
+///
+impl<V> IntoIterator for PermutedRandMap<V> {
+    type Item = (Handle<V>, V);
+    type IntoIter = PermutedIntoIter<V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        PermutedIntoIter {
+            round_keys: self.round_keys,
+            slots: self.slots.into_iter().enumerate(),
+        }
+    }
+}
+
+/// Does not show the key.
+impl<V> fmt::Debug for PermutedRandMap<V>
+where
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// The type returned by [`PermutedRandMap::iter()`
+/// ](struct.PermutedRandMap.html#method.iter).
+///
+pub struct PermutedIter<'a, V> {
+    round_keys: &'a [u64; ROUNDS],
+    slots: Enumerate<slice::Iter<'a, Slot<V>>>,
+}
+
+impl<'a, V> Iterator for PermutedIter<'a, V> {
+    type Item = (Handle<V>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let round_keys = self.round_keys;
+        self.slots.find_map(|(index, slot)| {
+            let value = slot.value.as_ref()?;
+            Some((encode(round_keys, index as u32, slot.generation), value))
+        })
+    }
+}
+
+/// The type returned by [`PermutedRandMap::iter_mut()`
+/// ](struct.PermutedRandMap.html#method.iter_mut).
+///
+pub struct PermutedIterMut<'a, V> {
+    round_keys: &'a [u64; ROUNDS],
+    slots: Enumerate<slice::IterMut<'a, Slot<V>>>,
+}
+
+impl<'a, V> Iterator for PermutedIterMut<'a, V> {
+    type Item = (Handle<V>, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let round_keys = self.round_keys;
+        self.slots.find_map(|(index, slot)| {
+            let value = slot.value.as_mut()?;
+            Some((encode(round_keys, index as u32, slot.generation), value))
+        })
+    }
+}
+
+/// The consuming iterator of a [`PermutedRandMap`
+/// ](struct.PermutedRandMap.html).
+///
+pub struct PermutedIntoIter<V> {
+    round_keys: [u64; ROUNDS],
+    slots: Enumerate<vec::IntoIter<Slot<V>>>,
+}
+
+impl<V> Iterator for PermutedIntoIter<V> {
+    type Item = (Handle<V>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let round_keys = &self.round_keys;
+        self.slots.find_map(|(index, slot)| {
+            let value = slot.value?;
+            Some((encode(round_keys, index as u32, slot.generation), value))
+        })
+    }
+}
+
+/// The type returned by [`PermutedRandMap::drain()`
+/// ](struct.PermutedRandMap.html#method.drain).
+///
+pub struct PermutedDrain<'a, V> {
+    map: &'a mut PermutedRandMap<V>,
+    // The next slot to take an item from.
+    index: usize,
+}
+
+impl<'a, V> Iterator for PermutedDrain<'a, V> {
+    type Item = (Handle<V>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.map.slots.len() {
+            let index = self.index;
+            self.index += 1;
+            let generation = self.map.slots[index].generation;
+            if let Some(value) = self.map.take(index) {
+                let round_keys = &self.map.round_keys;
+                return Some((
+                    encode(round_keys, index as u32, generation),
+                    value,
+                ));
+            }
+        }
+        None
+    }
+}
+
+impl<'a, V> Drop for PermutedDrain<'a, V> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+// Sends the slot `index` and its `generation` through the Feistel network.
+fn encode<V>(
+    round_keys: &[u64; ROUNDS],
+    index: u32,
+    generation: u32,
+) -> Handle<V> {
+    let (mut left, mut right) = (generation, index);
+    for &round_key in round_keys {
+        let f = round(right, round_key);
+        left = std::mem::replace(&mut right, left ^ f);
+    }
+    Handle::from_u64((left as u64) << 32 | right as u64)
+}
+
+// The round function of the Feistel network.
+#[inline]
+fn round(half: u32, round_key: u64) -> u32 {
+    (mix(half as u64 ^ round_key) >> 32) as u32
+}
+
+// The finalizer of SplitMix64.
+#[inline]
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
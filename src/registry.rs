@@ -0,0 +1,217 @@
+//! A map of values of many types, see [`RandRegistry`
+//! ](../struct.RandRegistry.html).
+
+use crate::{insert_fresh, DefaultRng, Handle};
+use hashers::null::PassThroughHasher;
+use rand::RngCore;
+use std::any::{Any, TypeId};
+use std::collections::hash_map::{self, HashMap};
+use std::fmt;
+use std::hash::BuildHasherDefault;
+use std::marker::PhantomData;
+
+// The handles of the registry proper, regardless of the value type.
+type AnyHandle = Handle<Box<dyn Any>>;
+
+/// A registry storing values of any `'static` type, as a replacement for
+/// many [`RandMap`](struct.RandMap.html)s side by side.
+///
+/// Inserting a `T` returns a typed `Handle<T>` for statically typed
+/// retrieval. All types share one handle space, so a handle is unique
+/// across the whole registry, not just among the values of its type. A
+/// `Handle<T>` never retrieves a value of a type other than `T`, even if
+/// forged with [`Handle::from_u64()`](struct.Handle.html#method.from_u64).
+///
+/// For debugging tools that know nothing of the types, [`get_any()`
+/// ](#method.get_any) looks up a raw handle and returns the value as
+/// `&dyn Any` along with its `TypeId`.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{Handle, RandRegistry};
+/// use std::any::TypeId;
+///
+/// let mut registry = RandRegistry::new();
+/// let name = registry.insert("foo".to_string());
+/// let age = registry.insert(42u32);
+/// assert_eq!(registry.get(name).unwrap(), "foo");
+/// *registry.get_mut(age).unwrap() += 1;
+/// assert_eq!(registry.get(age), Some(&43));
+///
+/// // The same raw handle, but the wrong type.
+/// let forged = Handle::<u64>::from_u64(age.as_u64());
+/// assert_eq!(registry.get(forged), None);
+///
+/// let (value, type_id) = registry.get_any(name.as_u64()).unwrap();
+/// assert_eq!(type_id, TypeId::of::<String>());
+/// assert_eq!(value.downcast_ref::<String>().unwrap(), "foo");
+/// assert_eq!(registry.iter::<u32>().count(), 1);
+/// assert_eq!(registry.remove(age), Some(43));
+/// assert_eq!(registry.len(), 1);
+/// ```
+pub struct RandRegistry<R = DefaultRng> {
+    items: HashMap<
+        AnyHandle,
+        Box<dyn Any>,
+        BuildHasherDefault<PassThroughHasher>,
+    >,
+    rng: R,
+}
+
+impl RandRegistry {
+    /// Creates an empty registry.
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_rng(DefaultRng)
+    }
+}
+
+impl<R> RandRegistry<R> {
+    /// Creates an empty registry that draws its handles from `rng`.
+    #[inline]
+    pub fn with_rng(rng: R) -> Self {
+        Self {
+            items: HashMap::default(),
+            rng,
+        }
+    }
+
+    #[inline]
+    pub fn rng(&self) -> &R {
+        &self.rng
+    }
+
+    #[inline]
+    pub fn rng_mut(&mut self) -> &mut R {
+        &mut self.rng
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.items.clear()
+    }
+
+    /// Whether a value of type `T` is stored under `handle`.
+    #[inline]
+    pub fn contains<T: Any>(&self, handle: Handle<T>) -> bool {
+        self.get(handle).is_some()
+    }
+
+    /// Retrieves a reference to the `T` stored under `handle`.
+    #[inline]
+    pub fn get<T: Any>(&self, handle: Handle<T>) -> Option<&T> {
+        self.items.get(&erase(handle))?.downcast_ref()
+    }
+
+    /// Retrieves a mutable reference to the `T` stored under `handle`.
+    #[inline]
+    pub fn get_mut<T: Any>(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        self.items.get_mut(&erase(handle))?.downcast_mut()
+    }
+
+    /// Retrieves the value stored under the raw `handle`, whatever its type,
+    /// along with the `TypeId` of that type.
+    #[inline]
+    pub fn get_any(&self, handle: u64) -> Option<(&dyn Any, TypeId)> {
+        let value = &**self.items.get(&Handle::from_u64(handle))?;
+        Some((value, value.type_id()))
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The iterator element type is `(Handle<T>, &T)`, covering the values
+    /// of type `T` only.
+    #[inline]
+    pub fn iter<T: Any>(&self) -> RegistryIter<'_, T> {
+        RegistryIter(self.items.iter(), PhantomData)
+    }
+
+    /// The number of values of all types.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Remove and return the `T` stored under `handle`. A value of another
+    /// type is left in place.
+    pub fn remove<T: Any>(&mut self, handle: Handle<T>) -> Option<T> {
+        match self.items.entry(erase(handle)) {
+            hash_map::Entry::Occupied(entry) if entry.get().is::<T>() => {
+                entry.remove().downcast().ok().map(|value| *value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Remove and return the value stored under the raw `handle`, whatever
+    /// its type.
+    #[inline]
+    pub fn remove_any(&mut self, handle: u64) -> Option<Box<dyn Any>> {
+        self.items.remove(&Handle::from_u64(handle))
+    }
+}
+
+impl<R> RandRegistry<R>
+where
+    R: RngCore,
+{
+    /// Insert a `T` and get a handle for retrieval, unique among the
+    /// handles of all types in the registry.
+    pub fn insert<T: Any>(&mut self, value: T) -> Handle<T> {
+        let handle =
+            insert_fresh(&mut self.items, Box::new(value), &mut self.rng);
+        Handle::from_u64(handle.as_u64())
+    }
+}
+
+/// Shows the raw handles and the `TypeId`s of the values.
+impl<R> fmt::Debug for RandRegistry<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.items
+                    .iter()
+                    .map(|(handle, value)| (handle, (**value).type_id())),
+            )
+            .finish()
+    }
+}
+
+impl<R> Default for RandRegistry<R>
+where
+    R: Default,
+{
+    fn default() -> Self {
+        Self::with_rng(R::default())
+    }
+}
+
+/// The type returned by [`RandRegistry::iter()`
+/// ](struct.RandRegistry.html#method.iter).
+///
+pub struct RegistryIter<'a, T>(
+    hash_map::Iter<'a, AnyHandle, Box<dyn Any>>,
+    PhantomData<&'a T>,
+);
+
+impl<'a, T> Iterator for RegistryIter<'a, T>
+where
+    T: Any,
+{
+    type Item = (Handle<T>, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().find_map(|(handle, value)| {
+            let value = value.downcast_ref()?;
+            Some((Handle::from_u64(handle.as_u64()), value))
+        })
+    }
+}
+
+#[inline]
+fn erase<T>(handle: Handle<T>) -> AnyHandle {
+    Handle::from_u64(handle.as_u64())
+}
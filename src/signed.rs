@@ -0,0 +1,249 @@
+//! A map with authenticated handles, see [`SignedRandMap`
+//! ](../struct.SignedRandMap.html).
+
+use crate::{DefaultRng, Handle, HandleEncoding, RandMap};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::error::Error;
+use std::fmt;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// The tag is the HMAC truncated to 128 bits.
+const TAG_LEN: usize = 16;
+const SEPARATOR: char = '.';
+// The most base32 digits of a handle and of a tag.
+const HANDLE_DIGITS: usize = 13;
+const TAG_DIGITS: usize = 26;
+
+/// A [`RandMap`](struct.RandMap.html) whose handles are signed when handed
+/// out, so that forged handles are rejected. Requires the `signed` feature.
+///
+/// The map holds a secret key. Externally, a handle is a string consisting
+/// of the handle proper and an HMAC-SHA256 tag, both in Crockford's base32,
+/// separated by a dot. [`verify()`](#method.verify) checks the tag before the
+/// handle is used for a lookup, so guessing or tampering with handles is of
+/// no use to a client.
+///
+/// Like any Crockford base32, signed handles are decoded leniently, i.e.
+/// regardless of case, hyphens and leading zeros, and with `I`, `L` and `O`
+/// read as digits. Beyond 13 digits for the handle proper and 26 for the
+/// tag, a signed handle is malformed.
+///
+/// [`rotate_key()`](#method.rotate_key) replaces the key. Handles signed by
+/// the previous key are still accepted, those signed by older keys are not.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{Handle, SignedRandMap, VerifyError};
+///
+/// let mut map = SignedRandMap::new(b"first secret");
+/// let foo = map.insert("foo");
+/// assert_eq!(map.verify_and_get(&foo), Ok(Some(&"foo")));
+///
+/// // A forged handle is rejected.
+/// let handle = map.verify(&foo).unwrap();
+/// let forged = format!("{}.{}", handle, "0123456789ABCDEFGHJKMNPQRS");
+/// assert_eq!(map.verify_and_get(&forged), Err(VerifyError::BadTag));
+/// assert_eq!(map.verify_and_get("junk"), Err(VerifyError::Malformed));
+///
+/// map.rotate_key(b"second secret");
+/// let bar = map.insert("bar");
+/// assert_eq!(map.verify_and_get(&foo), Ok(Some(&"foo")));
+/// map.rotate_key(b"third secret");
+/// assert_eq!(map.verify_and_get(&foo), Err(VerifyError::BadTag));
+/// assert_eq!(map.verify_and_get(&bar), Ok(Some(&"bar")));
+/// // Re-signing keeps a handle alive across rotations.
+/// let foo = map.sign(handle);
+/// assert_eq!(map.verify_and_remove(&foo), Ok(Some("foo")));
+/// assert_eq!(map.verify_and_get(&foo), Ok(None));
+/// ```
+pub struct SignedRandMap<V, R = DefaultRng> {
+    map: RandMap<V, R>,
+    key: HmacSha256,
+    previous_key: Option<HmacSha256>,
+}
+
+impl<V> SignedRandMap<V> {
+    /// Creates an empty map signing its handles with `key`.
+    #[inline]
+    pub fn new(key: &[u8]) -> Self {
+        Self::with_rng(key, DefaultRng)
+    }
+}
+
+impl<V, R> SignedRandMap<V, R> {
+    /// Creates an empty map signing its handles with `key` and drawing them
+    /// from `rng`.
+    #[inline]
+    pub fn with_rng(key: &[u8], rng: R) -> Self {
+        Self {
+            map: RandMap::with_rng(rng),
+            key: hmac(key),
+            previous_key: None,
+        }
+    }
+
+    /// Borrow the contained [`RandMap`](struct.RandMap.html), e.g. for
+    /// trusted code to use unsigned handles.
+    #[inline]
+    pub fn as_rand_map(&self) -> &RandMap<V, R> {
+        &self.map
+    }
+
+    /// Mutably borrow the contained [`RandMap`](struct.RandMap.html).
+    #[inline]
+    pub fn as_rand_map_mut(&mut self) -> &mut RandMap<V, R> {
+        &mut self.map
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Makes `key` the signing key. Handles signed by the replaced key are
+    /// still accepted, but no longer those signed by the key before that.
+    #[inline]
+    pub fn rotate_key(&mut self, key: &[u8]) {
+        self.previous_key = Some(std::mem::replace(&mut self.key, hmac(key)));
+    }
+
+    /// The external form of `handle`, signed by the current key.
+    pub fn sign(&self, handle: Handle<V>) -> String {
+        let tag = self.key.clone().chain_update(message(handle)).finalize();
+        let mut tag_bytes = [0; TAG_LEN];
+        tag_bytes.copy_from_slice(&tag.into_bytes()[..TAG_LEN]);
+        format!(
+            "{}{}{}",
+            handle,
+            SEPARATOR,
+            Handle::<V, u128>::from_u128(u128::from_be_bytes(tag_bytes))
+        )
+    }
+
+    /// Checks the tag of `signed`, against the current as well as the
+    /// previous key, and returns the handle proper.
+    pub fn verify(&self, signed: &str) -> Result<Handle<V>, VerifyError> {
+        let mut parts = signed.splitn(2, SEPARATOR);
+        let (handle, tag) = match (parts.next(), parts.next()) {
+            (Some(handle), Some(tag)) => (handle, tag),
+            _ => return Err(VerifyError::Malformed),
+        };
+        let digits = |part: &str| part.chars().filter(|&c| c != '-').count();
+        if digits(handle) > HANDLE_DIGITS || digits(tag) > TAG_DIGITS {
+            return Err(VerifyError::Malformed);
+        }
+        let decode = HandleEncoding::Crockford32;
+        let handle = Handle::<V>::decode(handle, decode)
+            .map_err(|_| VerifyError::Malformed)?;
+        let tag = Handle::<V, u128>::decode(tag, decode)
+            .map_err(|_| VerifyError::Malformed)?
+            .as_u128()
+            .to_be_bytes();
+        let verifies = |key: &HmacSha256| {
+            key.clone()
+                .chain_update(message(handle))
+                .verify_truncated_left(&tag)
+                .is_ok()
+        };
+        if verifies(&self.key) || self.previous_key.iter().any(verifies) {
+            Ok(handle)
+        } else {
+            Err(VerifyError::BadTag)
+        }
+    }
+
+    /// Retrieves a reference to a `V` using a signed handle. Returns
+    /// `Ok(None)` if the handle is genuine but its item is gone.
+    #[inline]
+    pub fn verify_and_get(
+        &self,
+        signed: &str,
+    ) -> Result<Option<&V>, VerifyError> {
+        self.verify(signed).map(|handle| self.map.get(handle))
+    }
+
+    /// Retrieves a mutable reference to a `V` using a signed handle. See
+    /// [`verify_and_get()`](#method.verify_and_get).
+    #[inline]
+    pub fn verify_and_get_mut(
+        &mut self,
+        signed: &str,
+    ) -> Result<Option<&mut V>, VerifyError> {
+        let handle = self.verify(signed)?;
+        Ok(self.map.get_mut(handle))
+    }
+
+    /// Remove and return a `V` using a signed handle. See
+    /// [`verify_and_get()`](#method.verify_and_get).
+    #[inline]
+    pub fn verify_and_remove(
+        &mut self,
+        signed: &str,
+    ) -> Result<Option<V>, VerifyError> {
+        let handle = self.verify(signed)?;
+        Ok(self.map.remove(handle))
+    }
+}
+
+impl<V, R> SignedRandMap<V, R>
+where
+    R: RngCore,
+{
+    /// Insert a `V` and get a signed handle for retrieval.
+    #[inline]
+    pub fn insert(&mut self, value: V) -> String {
+        let handle = self.map.insert(value);
+        self.sign(handle)
+    }
+}
+
+/// Does not show the keys.
+impl<V, R> fmt::Debug for SignedRandMap<V, R>
+where
+    V: fmt::Debug,
+    R: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignedRandMap")
+            .field("map", &self.map)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The error returned when a signed handle is rejected by a
+/// [`SignedRandMap`](struct.SignedRandMap.html).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VerifyError {
+    /// Not a signed handle at all.
+    Malformed,
+    /// The tag is wrong, i.e. the handle is forged, tampered with, or signed
+    /// by a key that is no longer accepted.
+    BadTag,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            VerifyError::Malformed => "malformed signed handle",
+            VerifyError::BadTag => "handle signature mismatch",
+        })
+    }
+}
+
+impl Error for VerifyError {}
+
+fn hmac(key: &[u8]) -> HmacSha256 {
+    HmacSha256::new_from_slice(key).expect("HMAC takes keys of any length")
+}
+
+fn message<V>(handle: Handle<V>) -> [u8; 8] {
+    handle.as_u64().to_be_bytes()
+}
@@ -0,0 +1,493 @@
+//! A map storing its items contiguously, see [`SlabRandMap`
+//! ](../struct.SlabRandMap.html).
+
+use crate::{insert_fresh, DefaultRng, Handle, HandleKey, RandMap};
+use hashers::null::PassThroughHasher;
+use rand::{Rng, RngCore};
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+use std::iter::{FromIterator, Zip};
+use std::{slice, vec};
+
+/// A [`RandMap`](struct.RandMap.html) look-alike keeping its items in a
+/// `Vec`, the slab, while the hash table only maps handles to slab indices.
+///
+/// Iteration walks the slab, which is fast, and items do not move when the
+/// table grows. [`values_slice()`](#method.values_slice) gives all items as
+/// a slice. The price is an extra indirection on lookup.
+///
+/// [`remove()`](#method.remove) moves the last item of the slab into the
+/// gap, so it takes constant time but changes the iteration order.
+/// Otherwise, items are iterated in order of insertion. See
+/// [`IndexRandMap`](struct.IndexRandMap.html) to keep that order.
+///
+/// The API mirrors that of `RandMap`, and the two convert into each other,
+/// keeping the handles.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{RandMap, SlabRandMap};
+///
+/// let mut map = SlabRandMap::new();
+/// let foo = map.insert("foo");
+/// let bar = map.insert("bar");
+/// let baz = map.insert("baz");
+/// assert_eq!(map.values_slice(), &["foo", "bar", "baz"]);
+/// assert_eq!(map.remove(foo), Some("foo"));
+/// assert_eq!(map.values_slice(), &["baz", "bar"]);
+/// assert_eq!(map.get(baz), Some(&"baz"));
+/// *map.get_mut(bar).unwrap() = "BAR";
+///
+/// let map: RandMap<_> = map.into();
+/// assert_eq!(map.get(bar), Some(&"BAR"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct SlabRandMap<V, R = DefaultRng, K = u64> {
+    pub(crate) index:
+        HashMap<Handle<V, K>, usize, BuildHasherDefault<PassThroughHasher>>,
+    pub(crate) handles: Vec<Handle<V, K>>,
+    pub(crate) values: Vec<V>,
+    rng: R,
+}
+
+impl<V> SlabRandMap<V> {
+    /// Creates an empty map.
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_rng(DefaultRng)
+    }
+}
+
+impl<V, R> SlabRandMap<V, R> {
+    /// Creates an empty map that draws its handles from `rng`.
+    #[inline]
+    pub fn with_rng(rng: R) -> Self {
+        Self::from_rng(rng)
+    }
+}
+
+impl<V, R, K> SlabRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    /// Like [`with_rng()`](#method.with_rng), but for any handle width `K`.
+    #[inline]
+    pub fn from_rng(rng: R) -> Self {
+        Self {
+            index: HashMap::default(),
+            handles: Vec::new(),
+            values: Vec::new(),
+            rng,
+        }
+    }
+
+    #[inline]
+    pub fn rng(&self) -> &R {
+        &self.rng
+    }
+
+    #[inline]
+    pub fn rng_mut(&mut self) -> &mut R {
+        &mut self.rng
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.index.clear();
+        self.handles.clear();
+        self.values.clear();
+    }
+
+    /// Clears the map, returning all handle-value pairs as an iterator, in
+    /// slab order.
+    #[inline]
+    pub fn drain(&mut self) -> SlabDrain<'_, V, K> {
+        self.index.clear();
+        SlabDrain(self.handles.drain(..).zip(self.values.drain(..)))
+    }
+
+    #[inline]
+    pub fn get(&self, handle: Handle<V, K>) -> Option<&V> {
+        let &index = self.index.get(&handle)?;
+        Some(&self.values[index])
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, handle: Handle<V, K>) -> Option<&mut V> {
+        let &index = self.index.get(&handle)?;
+        Some(&mut self.values[index])
+    }
+
+    /// The handles of the items, in slab order, i.e. `handles_slice()[i]`
+    /// is the handle of `values_slice()[i]`.
+    #[inline]
+    pub fn handles_slice(&self) -> &[Handle<V, K>] {
+        &self.handles
+    }
+
+    /// Like [`insert()`](#method.insert), but draws the handle from `rng`
+    /// rather than from the map's own random source.
+    pub fn insert_with_rng<G>(
+        &mut self,
+        value: V,
+        rng: &mut G,
+    ) -> Handle<V, K>
+    where
+        G: Rng + ?Sized,
+    {
+        let handle = insert_fresh(&mut self.index, self.values.len(), rng);
+        self.handles.push(handle);
+        self.values.push(value);
+        handle
+    }
+
+    /// Insert a key-value pair. An existing item with handle `key` is
+    /// replaced in place.
+    #[inline]
+    pub fn insert_key_value(&mut self, key: Handle<V, K>, value: V) {
+        self.replace_key_value(key, value);
+    }
+
+    /// Insert a key-value pair and return the value previously stored under
+    /// `key`, if any.
+    pub fn replace_key_value(
+        &mut self,
+        key: Handle<V, K>,
+        value: V,
+    ) -> Option<V> {
+        match self.index.get(&key) {
+            Some(&index) => {
+                Some(std::mem::replace(&mut self.values[index], value))
+            }
+            None => {
+                self.push(key, value);
+                None
+            }
+        }
+    }
+
+    /// Insert a key-value pair unless `key` is already present, in which
+    /// case the map is left untouched and `value` is given back.
+    pub fn try_insert_key_value(
+        &mut self,
+        key: Handle<V, K>,
+        value: V,
+    ) -> Result<(), V> {
+        if self.index.contains_key(&key) {
+            return Err(value);
+        }
+        self.push(key, value);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The iterator element type is `(Handle<V, K>, &V)`, in slab order.
+    #[inline]
+    pub fn iter(&self) -> SlabIter<'_, V, K> {
+        SlabIter(self.handles.iter().zip(self.values.iter()))
+    }
+
+    /// The iterator element type is `(Handle<V, K>, &mut V)`, in slab
+    /// order.
+    #[inline]
+    pub fn iter_mut(&mut self) -> SlabIterMut<'_, V, K> {
+        SlabIterMut(self.handles.iter().zip(self.values.iter_mut()))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Remove and return the value with handle `handle`, or `None` if not
+    /// found. The last item of the slab takes its place.
+    #[inline]
+    pub fn remove(&mut self, handle: Handle<V, K>) -> Option<V> {
+        let &index = self.index.get(&handle)?;
+        self.swap_remove_index(index).map(|(_, value)| value)
+    }
+
+    /// Retains only the items for which `f` returns `true`, keeping their
+    /// order.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Handle<V, K>, &mut V) -> bool,
+    {
+        let mut kept = 0;
+        for index in 0..self.values.len() {
+            if f(self.handles[index], &mut self.values[index]) {
+                self.handles.swap(kept, index);
+                self.values.swap(kept, index);
+                kept += 1;
+            }
+        }
+        for handle in self.handles.drain(kept..) {
+            self.index.remove(&handle);
+        }
+        self.values.truncate(kept);
+        self.reindex(0);
+    }
+
+    /// All items, in slab order.
+    #[inline]
+    pub fn values_slice(&self) -> &[V] {
+        &self.values
+    }
+
+    /// All items, in slab order.
+    #[inline]
+    pub fn values_slice_mut(&mut self) -> &mut [V] {
+        &mut self.values
+    }
+
+    fn push(&mut self, key: Handle<V, K>, value: V) {
+        self.index.insert(key, self.values.len());
+        self.handles.push(key);
+        self.values.push(value);
+    }
+
+    // Updates the slab indices of the items from index `from` on.
+    pub(crate) fn reindex(&mut self, from: usize) {
+        for (index, handle) in self.handles.iter().enumerate().skip(from) {
+            self.index.insert(*handle, index);
+        }
+    }
+
+    // Removes the item at slab index `index`, moving the last item into the
+    // gap.
+    pub(crate) fn swap_remove_index(
+        &mut self,
+        index: usize,
+    ) -> Option<(Handle<V, K>, V)> {
+        if index >= self.values.len() {
+            return None;
+        }
+        let handle = self.handles.swap_remove(index);
+        self.index.remove(&handle);
+        if let Some(&moved) = self.handles.get(index) {
+            self.index.insert(moved, index);
+        }
+        Some((handle, self.values.swap_remove(index)))
+    }
+}
+
+impl<V, R, K> SlabRandMap<V, R, K>
+where
+    R: RngCore,
+    K: HandleKey,
+{
+    /// Insert a `V` and get a handle for retrieval.
+    ///
+    /// # Panics
+    ///
+    /// Panics if every possible handle is in use.
+    #[inline]
+    pub fn insert(&mut self, value: V) -> Handle<V, K> {
+        let handle =
+            insert_fresh(&mut self.index, self.values.len(), &mut self.rng);
+        self.handles.push(handle);
+        self.values.push(value);
+        handle
+    }
+}
+
+impl<V, R, K> Default for SlabRandMap<V, R, K>
+where
+    R: Default,
+    K: HandleKey,
+{
+    fn default() -> Self {
+        Self::from_rng(R::default())
+    }
+}
+
+impl<'a, V, R, K> IntoIterator for &'a SlabRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, &'a V);
+    type IntoIter = SlabIter<'a, V, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, V, R, K> IntoIterator for &'a mut SlabRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, &'a mut V);
+    type IntoIter = SlabIterMut<'a, V, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// Consumes the map, the iterator element type is `(Handle<V, K>, V)`.
+///
+impl<V, R, K> IntoIterator for SlabRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, V);
+    type IntoIter = SlabIntoIter<V, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        SlabIntoIter(self.handles.into_iter().zip(self.values))
+    }
+}
+
+/// Like [`insert_key_value()`
+/// ](struct.SlabRandMap.html#method.insert_key_value), an existing item with
+/// the same handle is overwritten.
+impl<V, R, K> Extend<(Handle<V, K>, V)> for SlabRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    fn extend<I: IntoIterator<Item = (Handle<V, K>, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert_key_value(key, value);
+        }
+    }
+}
+
+/// The map gets `R::default()` as random source.
+impl<V, R, K> FromIterator<(Handle<V, K>, V)> for SlabRandMap<V, R, K>
+where
+    R: Default,
+    K: HandleKey,
+{
+    fn from_iter<I: IntoIterator<Item = (Handle<V, K>, V)>>(iter: I) -> Self {
+        let mut map = Self::default();
+        map.extend(iter);
+        map
+    }
+}
+
+/// Keeps the handles and the random source.
+impl<V, R, K> From<RandMap<V, R, K>> for SlabRandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    fn from(map: RandMap<V, R, K>) -> Self {
+        let RandMap(items, rng) = map;
+        let mut slab = Self::from_rng(rng);
+        slab.extend(items);
+        slab
+    }
+}
+
+/// Keeps the handles and the random source.
+impl<V, R, K> From<SlabRandMap<V, R, K>> for RandMap<V, R, K>
+where
+    K: HandleKey,
+{
+    fn from(slab: SlabRandMap<V, R, K>) -> Self {
+        let mut map = RandMap::from_rng(slab.rng);
+        map.extend(slab.handles.into_iter().zip(slab.values));
+        map
+    }
+}
+
+/// Only the items are compared, not their order or the random sources.
+impl<V, R, K> PartialEq for SlabRandMap<V, R, K>
+where
+    V: PartialEq,
+    K: HandleKey,
+{
+    fn eq(&self, other: &SlabRandMap<V, R, K>) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(key, val)| other.get(key) == Some(val))
+    }
+}
+
+/// The type returned by [`SlabRandMap::iter()`
+/// ](struct.SlabRandMap.html#method.iter).
+///
+pub struct SlabIter<'a, V, K = u64>(
+    Zip<slice::Iter<'a, Handle<V, K>>, slice::Iter<'a, V>>,
+);
+impl<'a, V, K> Iterator for SlabIter<'a, V, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (*k, v))
+    }
+}
+
+impl<'a, V, K> DoubleEndedIterator for SlabIter<'a, V, K>
+where
+    K: HandleKey,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(k, v)| (*k, v))
+    }
+}
+
+/// The type returned by [`SlabRandMap::iter_mut()`
+/// ](struct.SlabRandMap.html#method.iter_mut).
+///
+pub struct SlabIterMut<'a, V, K = u64>(
+    Zip<slice::Iter<'a, Handle<V, K>>, slice::IterMut<'a, V>>,
+);
+impl<'a, V, K> Iterator for SlabIterMut<'a, V, K>
+where
+    K: HandleKey,
+{
+    type Item = (Handle<V, K>, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (*k, v))
+    }
+}
+
+impl<'a, V, K> DoubleEndedIterator for SlabIterMut<'a, V, K>
+where
+    K: HandleKey,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(k, v)| (*k, v))
+    }
+}
+
+/// The consuming iterator of a [`SlabRandMap`](struct.SlabRandMap.html).
+///
+pub struct SlabIntoIter<V, K = u64>(
+    Zip<vec::IntoIter<Handle<V, K>>, vec::IntoIter<V>>,
+);
+impl<V, K> Iterator for SlabIntoIter<V, K> {
+    type Item = (Handle<V, K>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl<V, K> DoubleEndedIterator for SlabIntoIter<V, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back()
+    }
+}
+
+/// The type returned by [`SlabRandMap::drain()`
+/// ](struct.SlabRandMap.html#method.drain).
+///
+pub struct SlabDrain<'a, V, K = u64>(
+    Zip<vec::Drain<'a, Handle<V, K>>, vec::Drain<'a, V>>,
+);
+impl<'a, V, K> Iterator for SlabDrain<'a, V, K> {
+    type Item = (Handle<V, K>, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
@@ -0,0 +1,429 @@
+//! Saving and loading a [`RandMap`](../struct.RandMap.html) as a file.
+
+use crate::{Handle, HandleKey, RandMap};
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+const MAGIC: &[u8; 8] = b"RANDMAP\0";
+const VERSION: u32 = 1;
+// Magic, version, key width, item count and body length.
+const HEADER_LEN: usize = 8 + 4 + 1 + 8 + 8;
+const CHECKSUM_LEN: usize = 4;
+
+/// A value that can be saved in a snapshot of a [`RandMap`
+/// ](struct.RandMap.html), see [`RandMap::save_to()`
+/// ](struct.RandMap.html#method.save_to).
+///
+/// This is independent of the `serialize` feature. It is implemented for
+/// the integer types, `bool`, `String` and `Vec<u8>`.
+///
+/// ### Example:
+/// ```
+/// use rand_map::SnapshotValue;
+///
+/// struct Point(i32, i32);
+///
+/// impl SnapshotValue for Point {
+///     fn encode(&self, buf: &mut Vec<u8>) {
+///         self.0.encode(buf);
+///         self.1.encode(buf);
+///     }
+///
+///     fn decode(bytes: &[u8]) -> Option<Self> {
+///         if bytes.len() != 8 {
+///             return None;
+///         }
+///         let (x, y) = bytes.split_at(4);
+///         Some(Point(i32::decode(x)?, i32::decode(y)?))
+///     }
+/// }
+/// ```
+pub trait SnapshotValue: Sized {
+    /// Appends the encoding of `self` to `buf`.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Decodes exactly `bytes`, or returns `None` if they are invalid.
+    fn decode(bytes: &[u8]) -> Option<Self>;
+}
+
+macro_rules! snapshot_int {
+    ($($t:ty),*) => {$(
+        impl SnapshotValue for $t {
+            #[inline]
+            fn encode(&self, buf: &mut Vec<u8>) {
+                buf.extend_from_slice(&self.to_le_bytes());
+            }
+
+            #[inline]
+            fn decode(bytes: &[u8]) -> Option<Self> {
+                use std::convert::TryInto;
+                Some(Self::from_le_bytes(bytes.try_into().ok()?))
+            }
+        }
+    )*};
+}
+
+snapshot_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl SnapshotValue for bool {
+    #[inline]
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(*self as u8);
+    }
+
+    #[inline]
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0] => Some(false),
+            [1] => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl SnapshotValue for String {
+    #[inline]
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    #[inline]
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+impl SnapshotValue for Vec<u8> {
+    #[inline]
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self);
+    }
+
+    #[inline]
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        Some(bytes.to_vec())
+    }
+}
+
+/// The error returned when saving or loading a snapshot of a [`RandMap`
+/// ](struct.RandMap.html) fails.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// Reading or writing the file failed.
+    Io(io::Error),
+    /// The file is not a snapshot.
+    BadMagic,
+    /// The snapshot was written by a newer version of this crate.
+    UnsupportedVersion(u32),
+    /// The snapshot was saved with handles of another width, in bytes.
+    KeyWidthMismatch { expected: u8, found: u8 },
+    /// The file ends before the snapshot does.
+    Truncated,
+    /// The contents do not match the checksum.
+    ChecksumMismatch,
+    /// The checksum matches, yet the contents are invalid, e.g. a value
+    /// could not be decoded.
+    Corrupt,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "snapshot I/O error: {}", e),
+            SnapshotError::BadMagic => f.write_str("not a snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {}", version)
+            }
+            SnapshotError::KeyWidthMismatch { expected, found } => write!(
+                f,
+                "snapshot has {} byte handles, expected {}",
+                found, expected
+            ),
+            SnapshotError::Truncated => f.write_str("snapshot truncated"),
+            SnapshotError::ChecksumMismatch => {
+                f.write_str("snapshot checksum mismatch")
+            }
+            SnapshotError::Corrupt => f.write_str("snapshot corrupt"),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SnapshotError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl<V, R, K> RandMap<V, R, K>
+where
+    K: HandleKey,
+    V: SnapshotValue,
+{
+    /// Saves the items to the file at `path`, keeping their handles.
+    ///
+    /// The snapshot is written to a temporary file next to `path`, which
+    /// then replaces `path`, so a crash leaves either the old or the new
+    /// snapshot in place. Of concurrent saves to the same path, the last
+    /// one to finish wins.
+    ///
+    /// The format is versioned and checksummed. Items are written in order
+    /// of their handles, so equal maps give equal files.
+    ///
+    /// ### Example:
+    /// ```
+    /// use rand_map::RandMap;
+    ///
+    /// let path = std::env::temp_dir().join("rand_map_doc.snapshot");
+    /// let mut map = RandMap::new();
+    /// let foo = map.insert("foo".to_string());
+    /// map.save_to(&path).unwrap();
+    /// let loaded: RandMap<String> = RandMap::load_from(&path).unwrap();
+    /// assert_eq!(loaded, map);
+    /// assert_eq!(loaded.get(foo).unwrap(), "foo");
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn save_to<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<(), SnapshotError> {
+        let path = path.as_ref();
+        let mut items: Vec<_> = self.iter().collect();
+        items.sort_unstable_by_key(|(handle, _)| *handle);
+        let mut body = Vec::new();
+        for (handle, value) in items {
+            write_key(&mut body, handle.as_raw());
+            let len_at = body.len();
+            body.extend_from_slice(&[0; 8]);
+            value.encode(&mut body);
+            let len = (body.len() - len_at - 8) as u64;
+            body[len_at..len_at + 8].copy_from_slice(&len.to_le_bytes());
+        }
+        let mut file =
+            Vec::with_capacity(HEADER_LEN + body.len() + CHECKSUM_LEN);
+        file.extend_from_slice(MAGIC);
+        file.extend_from_slice(&VERSION.to_le_bytes());
+        file.push(key_width::<K>());
+        file.extend_from_slice(&(self.len() as u64).to_le_bytes());
+        file.extend_from_slice(&(body.len() as u64).to_le_bytes());
+        file.extend_from_slice(&body);
+        let checksum = crc32(&file);
+        file.extend_from_slice(&checksum.to_le_bytes());
+        write_atomically(path, &file)?;
+        Ok(())
+    }
+}
+
+impl<V, R, K> RandMap<V, R, K>
+where
+    K: HandleKey,
+    R: Default,
+    V: SnapshotValue,
+{
+    /// Loads a map saved by [`save_to()`](#method.save_to). The items have
+    /// the same handles as when saved.
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self, SnapshotError> {
+        let file = fs::read(path)?;
+        if file.len() < HEADER_LEN {
+            let n = file.len().min(MAGIC.len());
+            return Err(if file[..n] == MAGIC[..n] {
+                SnapshotError::Truncated
+            } else {
+                SnapshotError::BadMagic
+            });
+        }
+        let mut header = Reader(&file[..HEADER_LEN]);
+        if header.take(MAGIC.len()) != Some(&MAGIC[..]) {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = header.u32().ok_or(SnapshotError::Truncated)?;
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let width = header.take(1).ok_or(SnapshotError::Truncated)?[0];
+        if width != key_width::<K>() {
+            return Err(SnapshotError::KeyWidthMismatch {
+                expected: key_width::<K>(),
+                found: width,
+            });
+        }
+        let count = header.u64().ok_or(SnapshotError::Truncated)?;
+        let body_len = header.u64().ok_or(SnapshotError::Truncated)?;
+        let len = (HEADER_LEN as u64)
+            .checked_add(body_len)
+            .and_then(|len| len.checked_add(CHECKSUM_LEN as u64))
+            .ok_or(SnapshotError::Corrupt)?;
+        if (file.len() as u64) < len {
+            return Err(SnapshotError::Truncated);
+        }
+        if file.len() as u64 > len {
+            return Err(SnapshotError::Corrupt);
+        }
+        let (contents, checksum) = file.split_at(file.len() - CHECKSUM_LEN);
+        if Reader(checksum).u32() != Some(crc32(contents)) {
+            return Err(SnapshotError::ChecksumMismatch);
+        }
+
+        let mut body = Reader(&contents[HEADER_LEN..]);
+        let mut map = Self::default();
+        for _ in 0..count {
+            let key = body
+                .take(width as usize)
+                .and_then(read_key)
+                .ok_or(SnapshotError::Corrupt)?;
+            let value = body
+                .u64()
+                .and_then(|len| body.take(len as usize))
+                .and_then(V::decode)
+                .ok_or(SnapshotError::Corrupt)?;
+            if map
+                .try_insert_key_value(Handle::from_raw(key), value)
+                .is_err()
+            {
+                return Err(SnapshotError::Corrupt);
+            }
+        }
+        if !body.0.is_empty() {
+            return Err(SnapshotError::Corrupt);
+        }
+        Ok(map)
+    }
+}
+
+/// Writes `contents` to a temporary file next to `path`, and renames it to
+/// `path` once it is on disk. Concurrent writers each use their own
+/// temporary file, and the last rename wins.
+pub(crate) fn write_atomically(
+    path: &Path,
+    contents: &[u8],
+) -> io::Result<()> {
+    let (tmp, mut file) = create_tmp(path)?;
+    let result = (|| {
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+        return result;
+    }
+    sync_parent(path)
+}
+
+/// Makes a rename in the directory of `path` durable, where supported.
+pub(crate) fn sync_parent(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+// Creates a temporary file next to `path`, named after it, the process id
+// and a random suffix, so that no other writer opens the same one.
+fn create_tmp(path: &Path) -> io::Result<(PathBuf, File)> {
+    let name = path.file_name().map(OsString::from).unwrap_or_default();
+    loop {
+        let mut tmp_name = name.clone();
+        tmp_name.push(format!(
+            ".{}.{:08x}.tmp",
+            process::id(),
+            rand::random::<u32>()
+        ));
+        let tmp = path.with_file_name(tmp_name);
+        match OpenOptions::new().write(true).create_new(true).open(&tmp) {
+            Ok(file) => return Ok((tmp, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[inline]
+fn key_width<K: HandleKey>() -> u8 {
+    ((128 - K::MAX.leading_zeros()) / 8) as u8
+}
+
+#[inline]
+fn write_key<K: HandleKey>(buf: &mut Vec<u8>, key: K) {
+    let width = key_width::<K>() as usize;
+    buf.extend_from_slice(&key.to_u128().to_le_bytes()[..width]);
+}
+
+#[inline]
+fn read_key<K: HandleKey>(bytes: &[u8]) -> Option<K> {
+    let mut le = [0; 16];
+    le[..bytes.len()].copy_from_slice(bytes);
+    K::from_u128(u128::from_le_bytes(le))
+}
+
+/// A cursor over a byte slice, yielding `None` when it runs out.
+pub(crate) struct Reader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if n > self.0.len() {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        u32::decode(self.take(4)?)
+    }
+
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        u64::decode(self.take(8)?)
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                crc >> 1 ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// The CRC-32 (IEEE) of `bytes`.
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &b| {
+        CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ crc >> 8
+    })
+}
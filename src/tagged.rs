@@ -0,0 +1,271 @@
+//! A map recognizing its own handles, see [`TaggedRandMap`
+//! ](../struct.TaggedRandMap.html).
+
+use crate::{DefaultRng, Handle, Iter, RandMap};
+use rand::{Rng, RngCore};
+use std::error::Error;
+use std::fmt;
+
+const TAG_SHIFT: u32 = 48;
+const RANDOM_MASK: u64 = (1 << TAG_SHIFT) - 1;
+
+/// A [`RandMap`](struct.RandMap.html) whose handles carry the identity of
+/// the map, to catch handles used with the wrong map.
+///
+/// Each map has a random 16 bit tag, which makes up the upper bits of every
+/// handle it creates, the lower 48 bits being random as usual. Looking up a
+/// handle with another tag is an error, [`WrongMap`
+/// ](struct.WrongMap.html), rather than a silent miss or, worse, a hit on an
+/// unrelated item.
+///
+/// The check is on in debug builds and off in release builds, where a
+/// foreign handle merely finds nothing. Use [`set_checked()`
+/// ](#method.set_checked) to have it either way. Two maps get the same tag
+/// with a chance of 1 in 65536, so the check catches most mix-ups, not all.
+///
+/// ### Example:
+/// ```
+/// use rand_map::{TaggedRandMap, WrongMap};
+///
+/// let mut users = TaggedRandMap::with_tag(1);
+/// let mut admins = TaggedRandMap::with_tag(2);
+/// users.set_checked(true);
+/// let alice = users.insert("alice");
+/// let root = admins.insert("root");
+/// assert_eq!(alice.as_u64() >> 48, 1);
+/// assert_eq!(users.get(alice), Ok(Some(&"alice")));
+/// assert_eq!(
+///     users.get(root),
+///     Err(WrongMap { expected: 1, found: 2 })
+/// );
+/// users.set_checked(false);
+/// assert_eq!(users.get(root), Ok(None));
+/// ```
+#[derive(Clone, Debug)]
+pub struct TaggedRandMap<V, R = DefaultRng> {
+    map: RandMap<V, R>,
+    tag: u16,
+    checked: bool,
+}
+
+impl<V> TaggedRandMap<V> {
+    /// Creates an empty map with a random tag.
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_rng(DefaultRng)
+    }
+
+    /// Creates an empty map whose handles carry `tag`, e.g. to recognize
+    /// handles from an earlier run.
+    #[inline]
+    pub fn with_tag(tag: u16) -> Self {
+        Self::with_rng_and_tag(DefaultRng, tag)
+    }
+}
+
+impl<V, R> TaggedRandMap<V, R> {
+    /// Creates an empty map that draws its handles from `rng`, and its tag,
+    /// too.
+    #[inline]
+    pub fn with_rng(mut rng: R) -> Self
+    where
+        R: RngCore,
+    {
+        let tag = rng.gen();
+        Self::with_rng_and_tag(rng, tag)
+    }
+
+    /// Creates an empty map that draws its handles from `rng`, and whose
+    /// handles carry `tag`.
+    #[inline]
+    pub fn with_rng_and_tag(rng: R, tag: u16) -> Self {
+        Self {
+            map: RandMap::with_rng(rng),
+            tag,
+            checked: cfg!(debug_assertions),
+        }
+    }
+
+    /// Whether foreign handles are detected.
+    #[inline]
+    pub fn checked(&self) -> bool {
+        self.checked
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    /// Whether an item with handle `handle` is present.
+    #[inline]
+    pub fn contains(&self, handle: Handle<V>) -> Result<bool, WrongMap> {
+        Ok(self.get(handle)?.is_some())
+    }
+
+    /// Retrieves a reference to a `V` using the handle created by
+    /// [`insert()`](#method.insert).
+    #[inline]
+    pub fn get(&self, handle: Handle<V>) -> Result<Option<&V>, WrongMap> {
+        self.check(handle)?;
+        Ok(self.map.get(scramble(handle)))
+    }
+
+    /// Retrieves a mutable reference to a `V` using the handle created by
+    /// [`insert()`](#method.insert).
+    #[inline]
+    pub fn get_mut(
+        &mut self,
+        handle: Handle<V>,
+    ) -> Result<Option<&mut V>, WrongMap> {
+        self.check(handle)?;
+        Ok(self.map.get_mut(scramble(handle)))
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// The iterator element type is `(Handle<V>, &V)`.
+    #[inline]
+    pub fn iter(&self) -> TaggedIter<'_, V> {
+        TaggedIter(self.map.iter())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Remove and return the value with handle `handle`, or `None` if not
+    /// found.
+    #[inline]
+    pub fn remove(
+        &mut self,
+        handle: Handle<V>,
+    ) -> Result<Option<V>, WrongMap> {
+        self.check(handle)?;
+        Ok(self.map.remove(scramble(handle)))
+    }
+
+    /// Turns detection of foreign handles on or off, regardless of the build.
+    #[inline]
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked = checked
+    }
+
+    /// The tag carried by the handles of this map.
+    #[inline]
+    pub fn tag(&self) -> u16 {
+        self.tag
+    }
+
+    fn check(&self, handle: Handle<V>) -> Result<(), WrongMap> {
+        let found = (handle.as_u64() >> TAG_SHIFT) as u16;
+        if self.checked && found != self.tag {
+            Err(WrongMap {
+                expected: self.tag,
+                found,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<V, R> TaggedRandMap<V, R>
+where
+    R: RngCore,
+{
+    /// Insert a `V` and get a handle for retrieval, which carries the tag of
+    /// the map.
+    ///
+    /// # Panics
+    ///
+    /// If all 2^48 handles of the map are in use.
+    pub fn insert(&mut self, mut value: V) -> Handle<V> {
+        assert!(
+            self.map.len() as u64 <= RANDOM_MASK,
+            "every possible handle is in use"
+        );
+        let tag = u64::from(self.tag) << TAG_SHIFT;
+        loop {
+            let random = self.map.rng_mut().next_u64() & RANDOM_MASK;
+            let handle = Handle::from_u64(tag | random);
+            match self.map.try_insert_key_value(scramble(handle), value) {
+                Ok(()) => return handle,
+                Err(rejected) => value = rejected,
+            }
+        }
+    }
+}
+
+impl<V, R> Default for TaggedRandMap<V, R>
+where
+    R: RngCore + Default,
+{
+    fn default() -> Self {
+        Self::with_rng(R::default())
+    }
+}
+
+impl<'a, V, R> IntoIterator for &'a TaggedRandMap<V, R> {
+    type Item = (Handle<V>, &'a V);
+    type IntoIter = TaggedIter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// The type returned by [`TaggedRandMap::iter()`
+/// ](struct.TaggedRandMap.html#method.iter).
+///
+pub struct TaggedIter<'a, V>(Iter<'a, V>);
+
+impl<'a, V> Iterator for TaggedIter<'a, V> {
+    type Item = (Handle<V>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.0.next()?;
+        Some((unscramble(key), value))
+    }
+}
+
+/// The error returned when a [`TaggedRandMap`](struct.TaggedRandMap.html)
+/// is given a handle created by another map.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct WrongMap {
+    /// The tag of the map.
+    pub expected: u16,
+    /// The tag of the handle.
+    pub found: u16,
+}
+
+impl fmt::Display for WrongMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "handle belongs to map {:#06x}, not {:#06x}",
+            self.found, self.expected
+        )
+    }
+}
+
+impl Error for WrongMap {}
+
+// The items are stored under scrambled handles. The pass-through hasher
+// reverses the bytes of a handle, so stored as is, the tag would make up the
+// low 16 bits of every hash, which the table picks the first bucket to probe
+// by. All items would start probing at the same few buckets, making lookups
+// and inserts crawl along ever longer probe sequences.
+#[inline]
+fn scramble<V>(handle: Handle<V>) -> Handle<V> {
+    Handle::from_u64(crate::scramble(handle.as_u64()))
+}
+
+#[inline]
+fn unscramble<V>(key: Handle<V>) -> Handle<V> {
+    Handle::from_u64(crate::unscramble(key.as_u64()))
+}
@@ -0,0 +1,62 @@
+use rand_map::{BoundedRandMap, Lfu};
+
+#[test]
+fn lru_evicts_in_order_of_last_use() {
+    let mut map = BoundedRandMap::new(4);
+    let handles: Vec<_> = (0..4).map(|i| map.insert(i).0).collect();
+    // Use order, oldest first: 1, 3, 0, 2.
+    map.get(handles[1]);
+    map.get(handles[3]);
+    *map.get_mut(handles[0]).unwrap() += 10;
+    map.get(handles[2]);
+    // Peeking is not use.
+    assert_eq!(map.peek(handles[1]), Some(&1));
+    let evicted: Vec<_> = (4..8).map(|i| map.insert(i).1.unwrap()).collect();
+    assert_eq!(
+        evicted,
+        vec![
+            (handles[1], 1),
+            (handles[3], 3),
+            (handles[0], 10),
+            (handles[2], 2)
+        ]
+    );
+    assert_eq!(map.len(), 4);
+}
+
+#[test]
+fn lfu_evicts_least_used_then_least_recent() {
+    let mut map = BoundedRandMap::with_policy(3, Lfu::default());
+    let a = map.insert("a").0;
+    let b = map.insert("b").0;
+    let c = map.insert("c").0;
+    for _ in 0..3 {
+        map.get(a);
+    }
+    map.get(c);
+    map.get(b);
+    // a: 4 uses, b and c: 2 uses each, c used before b.
+    let (d, evicted) = map.insert("d");
+    assert_eq!(evicted, Some((c, "c")));
+    let (e, evicted) = map.insert("e");
+    assert_eq!(evicted, Some((d, "d")));
+    assert_eq!(map.evict(), Some((e, "e")));
+    assert_eq!(map.evict(), Some((b, "b")));
+    assert!(map.contains(a));
+}
+
+#[test]
+fn removal_and_clear_keep_policy_in_sync() {
+    let mut map = BoundedRandMap::new(2);
+    let a = map.insert("a").0;
+    let b = map.insert("b").0;
+    assert_eq!(map.remove(a), Some("a"));
+    assert_eq!(map.remove(a), None);
+    let (c, evicted) = map.insert("c");
+    assert_eq!(evicted, None);
+    assert_eq!(map.insert("d").1, Some((b, "b")));
+    map.clear();
+    assert_eq!(map.evict(), None);
+    assert!(!map.contains(c));
+    assert_eq!(map.insert("e").1, None);
+}
@@ -0,0 +1,39 @@
+use rand::{rngs::StdRng, SeedableRng};
+use rand_map::RandMap;
+
+#[test]
+fn reserved_room_is_not_reallocated() {
+    let mut map = RandMap::with_capacity(500);
+    let capacity = map.capacity();
+    assert!(capacity >= 500);
+    for i in 0..500 {
+        map.insert(i);
+    }
+    assert_eq!(map.capacity(), capacity);
+    map.retain(|_, v| *v < 10);
+    map.shrink_to(100);
+    assert!(map.capacity() >= 100 && map.capacity() < capacity);
+    map.shrink_to_fit();
+    assert!(map.capacity() >= 10 && map.capacity() < 100);
+    map.reserve(1000);
+    assert!(map.capacity() >= 1010);
+}
+
+#[test]
+fn builder_applies_all_options() {
+    let build = || {
+        RandMap::builder()
+            .capacity(64)
+            .rng(StdRng::seed_from_u64(4711))
+            .key::<u128>()
+            .try_build()
+            .unwrap()
+    };
+    let (mut map1, mut map2) = (build(), build());
+    assert!(map1.capacity() >= 64);
+    for value in 0..64 {
+        assert_eq!(map1.insert(value), map2.insert(value));
+    }
+    assert_eq!(std::mem::size_of_val(&map1.insert(64)), 16);
+    assert_eq!(map1.len(), 65);
+}
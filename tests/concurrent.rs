@@ -0,0 +1,85 @@
+use rand_map::{ConcurrentRandMap, Handle};
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+const THREADS: usize = 8;
+const PER_THREAD: usize = 2000;
+
+#[test]
+fn stress_under_contention() {
+    // Few shards, to make the threads fight over the locks.
+    let map = Arc::new(ConcurrentRandMap::with_shard_bits(1));
+    let barrier = Arc::new(Barrier::new(THREADS));
+    let workers: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            let barrier = Arc::clone(&barrier);
+            thread::spawn(move || {
+                barrier.wait();
+                let handles: Vec<Handle<(usize, usize)>> =
+                    (0..PER_THREAD).map(|i| map.insert((t, i))).collect();
+                for (i, handle) in handles.iter().enumerate() {
+                    assert_eq!(map.get(*handle), Some((t, i)));
+                    assert_eq!(
+                        map.update(*handle, |v| {
+                            v.1 += PER_THREAD;
+                            v.1
+                        }),
+                        Some(i + PER_THREAD)
+                    );
+                }
+                for (i, handle) in handles.iter().enumerate().step_by(2) {
+                    assert_eq!(
+                        map.remove(*handle),
+                        Some((t, i + PER_THREAD))
+                    );
+                    assert!(!map.contains(*handle));
+                }
+                handles
+            })
+        })
+        .collect();
+    let handles: Vec<_> =
+        workers.into_iter().map(|w| w.join().unwrap()).collect();
+    assert_eq!(map.len(), THREADS * PER_THREAD / 2);
+    for (t, handles) in handles.iter().enumerate() {
+        for (i, handle) in handles.iter().enumerate() {
+            let expected = if i % 2 == 0 {
+                None
+            } else {
+                Some((t, i + PER_THREAD))
+            };
+            assert_eq!(map.get_with(*handle, |v| *v), expected);
+        }
+    }
+    let map = Arc::try_unwrap(map).unwrap().into_rand_map();
+    assert_eq!(map.len(), THREADS * PER_THREAD / 2);
+}
+
+#[test]
+fn readers_and_writers() {
+    let map = Arc::new(ConcurrentRandMap::new());
+    let fixed: Vec<_> = (0..100).map(|i| map.insert(i)).collect();
+    let fixed = Arc::new(fixed);
+    let workers: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            let fixed = Arc::clone(&fixed);
+            thread::spawn(move || {
+                for round in 0..PER_THREAD {
+                    if t % 2 == 0 {
+                        let handle = map.insert(round);
+                        assert_eq!(map.remove(handle), Some(round));
+                    } else {
+                        let i = round % fixed.len();
+                        assert_eq!(map.get(fixed[i]), Some(i));
+                    }
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    assert_eq!(map.len(), 100);
+}
@@ -0,0 +1,210 @@
+use rand_map::{DurableRandMap, Handle, RandMap, WalError};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join("rand_map_durable_tests")
+        .join(name);
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn contents(map: &DurableRandMap<String>) -> HashMap<u64, String> {
+    map.iter()
+        .map(|(handle, value)| (handle.as_u64(), value.clone()))
+        .collect()
+}
+
+// Runs a mix of operations, and returns the log length and the contents
+// after each.
+fn history(
+    map: &mut DurableRandMap<String>,
+) -> Vec<(u64, HashMap<u64, String>)> {
+    let log = map.dir().join("log");
+    let mut history =
+        vec![(fs::metadata(&log).unwrap().len(), contents(map))];
+    let mut record = |map: &mut DurableRandMap<String>| {
+        history.push((fs::metadata(&log).unwrap().len(), contents(map)));
+    };
+    let mut handles = Vec::new();
+    for i in 0..6 {
+        handles.push(map.insert(format!("value {}", i)).unwrap());
+        record(map);
+    }
+    map.remove(handles[1]).unwrap();
+    record(map);
+    let mut value = map.get_mut(handles[2]).unwrap();
+    value.push_str(" changed");
+    value.commit().unwrap();
+    record(map);
+    map.update(handles[3], |v| v.clear()).unwrap();
+    record(map);
+    map.insert_key_value(Handle::from_u64(4711), "chosen".to_string())
+        .unwrap();
+    record(map);
+    map.clear().unwrap();
+    record(map);
+    map.insert("after clear".to_string()).unwrap();
+    record(map);
+    history
+}
+
+#[test]
+fn reopen_restores_contents() {
+    let dir = temp_dir("reopen");
+    let mut map = DurableRandMap::open(&dir).unwrap();
+    let history = history(&mut map);
+    drop(map);
+    let map = DurableRandMap::<String>::open(&dir).unwrap();
+    assert_eq!(contents(&map), history.last().unwrap().1);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crash_at_any_offset_recovers_a_prefix() {
+    let dir = temp_dir("crash_source");
+    let mut map = DurableRandMap::open(&dir).unwrap();
+    let history = history(&mut map);
+    drop(map);
+    let log = fs::read(dir.join("log")).unwrap();
+    let crashed = temp_dir("crash");
+    for offset in 0..=log.len() {
+        fs::create_dir_all(&crashed).unwrap();
+        fs::write(crashed.join("log"), &log[..offset]).unwrap();
+        let mut map = DurableRandMap::<String>::open(&crashed).unwrap();
+        let expected = history
+            .iter()
+            .rev()
+            .find(|(len, _)| *len <= offset as u64)
+            .map(|(_, contents)| contents.clone())
+            .unwrap_or_default();
+        assert_eq!(contents(&map), expected, "offset {}", offset);
+        // The torn tail is gone, so the log can be appended to again.
+        let handle = map.insert("new".to_string()).unwrap();
+        drop(map);
+        let map = DurableRandMap::<String>::open(&crashed).unwrap();
+        assert_eq!(map.get(handle).unwrap(), "new");
+        assert_eq!(map.len(), expected.len() + 1);
+        fs::remove_dir_all(&crashed).unwrap();
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn corruption_before_the_end_is_an_error() {
+    let dir = temp_dir("corrupt");
+    let mut map = DurableRandMap::open(&dir).unwrap();
+    map.insert("foo".to_string()).unwrap();
+    map.insert("bar".to_string()).unwrap();
+    drop(map);
+    let mut log = fs::read(dir.join("log")).unwrap();
+    // The value of the first record.
+    log[34] ^= 1;
+    fs::write(dir.join("log"), &log).unwrap();
+    assert!(matches!(
+        DurableRandMap::<String>::open(&dir),
+        Err(WalError::Corrupt { offset: 12 })
+    ));
+    fs::write(dir.join("log"), b"not a log").unwrap();
+    assert!(matches!(
+        DurableRandMap::<String>::open(&dir),
+        Err(WalError::Corrupt { offset: 0 })
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn damaged_length_is_not_a_torn_record() {
+    let dir = temp_dir("damaged_length");
+    let mut map = DurableRandMap::open(&dir).unwrap();
+    let mut offsets = Vec::new();
+    for i in 0..3 {
+        offsets.push(fs::metadata(dir.join("log")).unwrap().len() as usize);
+        map.insert(format!("value {}", i)).unwrap();
+    }
+    drop(map);
+    let log = fs::read(dir.join("log")).unwrap();
+    // Each bit of the length of each record but the last, which would then
+    // run past the end of the log, or end within it.
+    for &offset in &offsets[..2] {
+        for bit in 0..32 {
+            let mut damaged = log.clone();
+            damaged[offset + bit / 8] ^= 1 << (bit % 8);
+            fs::write(dir.join("log"), &damaged).unwrap();
+            let result = DurableRandMap::<String>::open(&dir);
+            match result {
+                Err(WalError::Corrupt { offset: found }) => {
+                    assert_eq!(found, offset as u64, "bit {}", bit)
+                }
+                _ => panic!("offset {}, bit {}: not corrupt", offset, bit),
+            }
+            assert_eq!(fs::read(dir.join("log")).unwrap(), damaged);
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn torn_header_at_the_end_is_dropped() {
+    let dir = temp_dir("torn_header");
+    let mut map = DurableRandMap::open(&dir).unwrap();
+    let foo = map.insert("foo".to_string()).unwrap();
+    drop(map);
+    let log = fs::read(dir.join("log")).unwrap();
+    let mut half_written = log[12..24].to_vec();
+    half_written[6..].iter_mut().for_each(|byte| *byte = 0);
+    // A zero-filled header, a half-written one, and a zero-filled record.
+    for tail in &[vec![0; 12], half_written, vec![0; 40]] {
+        let mut torn = log.clone();
+        torn.extend_from_slice(tail);
+        fs::write(dir.join("log"), &torn).unwrap();
+        let mut map = DurableRandMap::<String>::open(&dir).unwrap();
+        assert_eq!(map.get(foo).unwrap(), "foo");
+        assert_eq!(map.len(), 1);
+        let bar = map.insert("bar".to_string()).unwrap();
+        drop(map);
+        let map = DurableRandMap::<String>::open(&dir).unwrap();
+        assert_eq!(map.get(bar).unwrap(), "bar");
+        assert_eq!(map.len(), 2);
+    }
+    // Something other than zeros after a damaged header is corruption.
+    let mut damaged = log.clone();
+    damaged.extend_from_slice(&[0; 12]);
+    damaged.push(1);
+    fs::write(dir.join("log"), &damaged).unwrap();
+    assert!(matches!(
+        DurableRandMap::<String>::open(&dir),
+        Err(WalError::Corrupt { offset }) if offset == log.len() as u64
+    ));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compaction() {
+    let dir = temp_dir("compaction");
+    let mut map = DurableRandMap::open(&dir).unwrap();
+    let history = history(&mut map);
+    map.compact().unwrap();
+    assert_eq!(fs::metadata(dir.join("log")).unwrap().len(), 12);
+    let snapshot: RandMap<String> =
+        RandMap::load_from(dir.join("snapshot")).unwrap();
+    assert_eq!(snapshot.len(), map.len());
+    let foo = map.insert("foo".to_string()).unwrap();
+    map.remove(snapshot.iter().next().unwrap().0).unwrap();
+    drop(map);
+    let mut map = DurableRandMap::<String>::open(&dir).unwrap();
+    assert_eq!(map.get(foo).unwrap(), "foo");
+    assert_eq!(map.len(), history.last().unwrap().1.len());
+    // A crash after the snapshot was replaced, but before the log was
+    // emptied, leaves the old log to be replayed onto the new snapshot.
+    let old_log = fs::read(dir.join("log")).unwrap();
+    map.compact().unwrap();
+    let expected = contents(&map);
+    drop(map);
+    fs::write(dir.join("log"), &old_log).unwrap();
+    let map = DurableRandMap::<String>::open(&dir).unwrap();
+    assert_eq!(contents(&map), expected);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,111 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_map::{Handle, HandleEncoding, ParseHandleError};
+
+const ENCODINGS: [HandleEncoding; 3] = [
+    HandleEncoding::Crockford32,
+    HandleEncoding::Base62,
+    HandleEncoding::Hex,
+];
+
+fn samples() -> impl Iterator<Item = u64> {
+    let edges = (0..64).flat_map(|bit| {
+        let u = 1u64 << bit;
+        vec![u - 1, u, u + 1]
+    });
+    let random = StdRng::seed_from_u64(4711)
+        .sample_iter(rand::distributions::Standard)
+        .take(100_000);
+    edges.chain(vec![u64::MAX - 1, u64::MAX]).chain(random)
+}
+
+#[test]
+fn round_trip() {
+    for u in samples() {
+        let handle = Handle::<()>::from_u64(u);
+        for &encoding in &ENCODINGS {
+            let s = handle.encode(encoding);
+            assert_eq!(Handle::decode(&s, encoding), Ok(handle), "{}", s);
+        }
+        assert_eq!(handle.to_string().parse(), Ok(handle));
+    }
+}
+
+#[test]
+fn compact_and_url_safe() {
+    for u in samples() {
+        let handle = Handle::<()>::from_u64(u);
+        for &(encoding, max_len) in &[
+            (HandleEncoding::Crockford32, 13),
+            (HandleEncoding::Base62, 11),
+            (HandleEncoding::Hex, 16),
+        ] {
+            let s = handle.encode(encoding);
+            assert!(s.len() <= max_len, "{}", s);
+            assert!(s.chars().all(|c| c.is_ascii_alphanumeric()), "{}", s);
+            assert!(u == 0 || !s.starts_with('0'), "{}", s);
+        }
+    }
+    assert_eq!(Handle::<()>::from_u64(0).to_string(), "0");
+    assert_eq!(
+        Handle::<()>::from_u64(u64::MAX).to_string(),
+        "FZZZZZZZZZZZZ"
+    );
+}
+
+#[test]
+fn crockford_is_forgiving() {
+    let mut rng = StdRng::seed_from_u64(17);
+    for _ in 0..1000 {
+        let handle: Handle<()> = rng.gen();
+        let s = handle.to_string();
+        assert_eq!(s.to_lowercase().parse(), Ok(handle));
+        let confusable = s.replace('1', "l").replace('0', "O");
+        assert_eq!(confusable.parse(), Ok(handle));
+        let hyphenated: String = s
+            .chars()
+            .enumerate()
+            .flat_map(
+                |(i, c)| if i % 4 == 3 { vec![c, '-'] } else { vec![c] },
+            )
+            .collect();
+        assert_eq!(hyphenated.parse(), Ok(handle));
+    }
+}
+
+#[test]
+fn parse_errors() {
+    assert_eq!("".parse::<Handle<()>>(), Err(ParseHandleError::Empty));
+    assert_eq!("--".parse::<Handle<()>>(), Err(ParseHandleError::Empty));
+    assert_eq!(
+        "12U4".parse::<Handle<()>>(),
+        Err(ParseHandleError::InvalidDigit {
+            index: 2,
+            digit: 'U'
+        })
+    );
+    assert_eq!(
+        "GZZZZZZZZZZZZ".parse::<Handle<()>>(),
+        Err(ParseHandleError::Overflow)
+    );
+    assert_eq!(
+        "1ZZZZZZZZZZZZZ".parse::<Handle<()>>(),
+        Err(ParseHandleError::Overflow)
+    );
+    assert_eq!(
+        Handle::<()>::decode("a-b", HandleEncoding::Base62),
+        Err(ParseHandleError::InvalidDigit {
+            index: 1,
+            digit: '-'
+        })
+    );
+    assert_eq!(
+        Handle::<()>::decode("10000000000000000", HandleEncoding::Hex),
+        Err(ParseHandleError::Overflow)
+    );
+    let max = Handle::<()>::from_u64(u64::MAX).encode(HandleEncoding::Base62);
+    assert_eq!(max, "LygHa16AHYF");
+    assert_eq!(
+        Handle::<()>::decode("LygHa16AHYG", HandleEncoding::Base62),
+        Err(ParseHandleError::Overflow)
+    );
+}
@@ -0,0 +1,66 @@
+use rand_map::{ExpiringRandMap, ManualClock};
+use std::collections::HashSet;
+use std::time::Duration;
+
+#[test]
+fn purge_returns_exactly_the_expired_items() {
+    let clock = ManualClock::new();
+    let mut map = ExpiringRandMap::with_clock(clock.clone());
+    let handles: Vec<_> = (0..100u64)
+        .map(|i| map.insert_with_ttl(i, Duration::from_secs(i)))
+        .collect();
+    let forever = map.insert(4711);
+    clock.advance(Duration::from_secs(50));
+    let mut purged = map.purge_expired();
+    purged.sort_by_key(|&(_, value)| value);
+    let expected: Vec<_> =
+        (0..=50u64).map(|i| (handles[i as usize], i)).collect();
+    assert_eq!(purged, expected);
+    assert_eq!(map.len(), 50);
+    assert!(map.purge_expired().is_empty());
+    assert_eq!(map.iter().count(), 50);
+    assert_eq!((&map).into_iter().count(), 50);
+    assert_eq!(map.get(forever), Some(&4711));
+    clock.advance(Duration::from_secs(u32::MAX.into()));
+    assert_eq!(map.purge_expired().len(), 49);
+    assert_eq!(
+        map.iter().map(|(handle, _)| handle).collect::<HashSet<_>>(),
+        [forever].iter().copied().collect()
+    );
+}
+
+#[test]
+fn lazy_expiry() {
+    let clock = ManualClock::new();
+    let mut map = ExpiringRandMap::with_clock(clock.clone());
+    let foo = map.insert_with_ttl("foo", Duration::from_secs(1));
+    let bar = map.insert_with_ttl("bar", Duration::from_secs(1));
+    clock.advance(Duration::from_millis(999));
+    assert!(map.contains(foo));
+    assert_eq!(map.ttl(foo), Some(Duration::from_millis(1)));
+    clock.advance(Duration::from_millis(1));
+    assert!(!map.contains(foo));
+    assert_eq!(map.ttl(foo), None);
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.remove(foo), None);
+    assert_eq!(map.get_mut(bar), None);
+    assert!(map.is_empty());
+}
+
+#[test]
+fn ttl_can_be_changed() {
+    let clock = ManualClock::new();
+    let mut map = ExpiringRandMap::with_clock(clock.clone());
+    let foo = map.insert_with_ttl("foo", Duration::from_secs(1));
+    let bar = map.insert("bar");
+    assert!(map.set_ttl(foo, None));
+    assert!(map.set_ttl(bar, Some(Duration::from_secs(1))));
+    clock.advance(Duration::from_secs(1));
+    assert!(!map.set_ttl(bar, None));
+    assert_eq!(map.purge_expired(), vec![(bar, "bar")]);
+    assert_eq!(map.remove(foo), Some("foo"));
+    // A TTL beyond what the clock can represent means no expiry.
+    let baz = map.insert_with_ttl("baz", Duration::MAX);
+    assert_eq!(map.ttl(baz), None);
+    assert_eq!(map.get(baz), Some(&"baz"));
+}
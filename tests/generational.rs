@@ -0,0 +1,81 @@
+use rand::{rngs::StdRng, SeedableRng};
+use rand_map::{GenRandMap, Handle, StaleHandle};
+use std::collections::HashMap;
+
+#[test]
+fn stale_handles_are_rejected() {
+    let mut map = GenRandMap::new();
+    let foo = map.insert("foo".to_string());
+    assert_eq!(map.remove(foo), Ok(Some("foo".to_string())));
+    assert_eq!(map.get(foo), Err(StaleHandle));
+    assert_eq!(map.get_mut(foo), Err(StaleHandle));
+    assert_eq!(map.remove(foo), Err(StaleHandle));
+    assert!(map.is_empty());
+
+    // Forgotten slots make for unknown handles rather than stale ones.
+    map.clear_removed();
+    assert_eq!(map.get(foo), Ok(None));
+    assert_eq!(map.remove(foo), Ok(None));
+}
+
+#[test]
+fn overwriting_a_live_item_makes_its_handle_stale() {
+    let mut map = GenRandMap::new();
+    let foo = map.insert("foo");
+    // The current handle replaces the item in place.
+    assert_eq!(map.insert_key_value(foo, "FOO"), foo);
+    assert_eq!(map.get(foo), Ok(Some(&"FOO")));
+    // Another generation of the same slot replaces it, too, but under a
+    // fresh generation.
+    let other = Handle::from_u64(foo.as_u64() ^ 5);
+    let bar = map.insert_key_value(other, "bar");
+    assert_ne!(bar, foo);
+    assert_ne!(bar, other);
+    assert_eq!(bar.as_u64() >> 16, foo.as_u64() >> 16);
+    assert_eq!(map.get(foo), Err(StaleHandle));
+    assert_eq!(map.get(bar), Ok(Some(&"bar")));
+    *map.get_mut(bar).unwrap().unwrap() = "BAR";
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.remove(bar), Ok(Some("BAR")));
+}
+
+#[test]
+fn generations_wrap_around() {
+    let mut map = GenRandMap::new();
+    let first = map.insert(0);
+    let mut handle = first;
+    for i in 1..=u16::MAX as u32 {
+        assert_eq!(map.remove(handle), Ok(Some(i - 1)));
+        handle = map.insert_key_value(handle, i);
+        assert_eq!(handle.as_u64() & 0xffff, u64::from(i));
+    }
+    assert_eq!(map.remove(handle), Ok(Some(u16::MAX as u32)));
+    // After 65536 generations, the first handle is taken as current again.
+    assert_eq!(map.insert_key_value(handle, 4711), first);
+    assert_eq!(map.get(first), Ok(Some(&4711)));
+}
+
+#[test]
+fn iteration_yields_current_handles() {
+    let mut map = GenRandMap::with_rng(StdRng::seed_from_u64(4711));
+    let mut model = HashMap::new();
+    for i in 0..1_000 {
+        model.insert(map.insert(i), i);
+    }
+    let removed: Vec<_> = model.keys().copied().take(300).collect();
+    for handle in removed {
+        let value = model.remove(&handle).unwrap();
+        assert_eq!(map.remove(handle), Ok(Some(value)));
+        if value % 2 == 0 {
+            let handle = map.insert_key_value(handle, value + 1_000);
+            model.insert(handle, value + 1_000);
+        }
+    }
+    let items: HashMap<_, _> =
+        map.iter().map(|(handle, &value)| (handle, value)).collect();
+    assert_eq!(items, model);
+    assert_eq!((&map).into_iter().count(), map.len());
+    for (handle, value) in &model {
+        assert_eq!(map.get(*handle), Ok(Some(value)));
+    }
+}
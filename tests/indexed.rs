@@ -0,0 +1,62 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_map::{Handle, IndexRandMap};
+
+#[test]
+fn order_follows_a_vec_model() {
+    let mut rng = StdRng::seed_from_u64(4711);
+    let mut map = IndexRandMap::new();
+    let mut model: Vec<(Handle<u32>, u32)> = Vec::new();
+    for step in 0..5_000 {
+        match rng.gen_range(0..5) {
+            0 | 1 => {
+                let handle = map.insert(step);
+                model.push((handle, step));
+            }
+            2 if !model.is_empty() => {
+                let index = rng.gen_range(0..model.len());
+                let (handle, value) = model.remove(index);
+                assert_eq!(map.shift_remove(handle), Some(value));
+            }
+            3 if !model.is_empty() => {
+                let index = rng.gen_range(0..model.len());
+                let item = model.swap_remove(index);
+                assert_eq!(map.swap_remove_index(index), Some(item));
+            }
+            _ if !model.is_empty() => {
+                let index = rng.gen_range(0..model.len());
+                let (handle, value) = model[index];
+                map.insert_key_value(handle, value + 1);
+                model[index].1 += 1;
+            }
+            _ => (),
+        }
+    }
+    let items: Vec<_> = map.iter().map(|(h, v)| (h, *v)).collect();
+    assert_eq!(items, model);
+    for (index, &(handle, value)) in model.iter().enumerate() {
+        assert_eq!(map.index_of(handle), Some(index));
+        assert_eq!(map.get_index(index), Some((handle, &value)));
+    }
+    assert_eq!(map.get_index(model.len()), None);
+    assert_eq!(map.into_iter().collect::<Vec<_>>(), model);
+}
+
+#[test]
+fn retain_keeps_order() {
+    let mut map = IndexRandMap::new();
+    let handles: Vec<_> = (0..20).map(|i| map.insert(i)).collect();
+    map.retain(|_, value| *value % 2 == 1);
+    let values: Vec<_> = map.iter().map(|(_, &v)| v).collect();
+    assert_eq!(values, (1..20).step_by(2).collect::<Vec<_>>());
+    for (i, handle) in handles.iter().enumerate() {
+        match map.index_of(*handle) {
+            Some(index) => assert_eq!(index, i / 2),
+            None => assert_eq!(i % 2, 0),
+        }
+    }
+    for (_, value) in &mut map {
+        *value *= 10;
+    }
+    assert_eq!(map.get_index(0), Some((handles[1], &10)));
+    assert_eq!(map.iter().next_back(), Some((handles[19], &190)));
+}
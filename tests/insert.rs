@@ -0,0 +1,67 @@
+use rand::rngs::mock::StepRng;
+use rand_map::{Handle, RandMap};
+
+// A source that yields 5, 6, 7, and so on.
+fn rigged() -> StepRng {
+    StepRng::new(5, 1)
+}
+
+#[test]
+fn insert_skips_occupied_handles() {
+    let mut map = RandMap::with_rng(rigged());
+    map.insert_key_value(Handle::from_u64(5), "old");
+    map.insert_key_value(Handle::from_u64(7), "older");
+    assert_eq!(map.insert("new"), Handle::from_u64(6));
+    assert_eq!(map.insert("newer"), Handle::from_u64(8));
+    assert_eq!(map.get(Handle::from_u64(5)), Some(&"old"));
+    assert_eq!(map.get(Handle::from_u64(6)), Some(&"new"));
+    assert_eq!(map.get(Handle::from_u64(7)), Some(&"older"));
+    assert_eq!(map.get(Handle::from_u64(8)), Some(&"newer"));
+    assert_eq!(map.len(), 4);
+}
+
+#[test]
+fn insert_with_rng_and_insert_many_skip_occupied_handles() {
+    let mut map = RandMap::new();
+    map.insert_key_value(Handle::from_u64(5), "old");
+    let handle = map.insert_with_rng("new", &mut rigged());
+    assert_eq!(handle, Handle::from_u64(6));
+
+    let mut map = RandMap::with_rng(rigged());
+    map.insert_key_value(Handle::from_u64(6), "old");
+    let handles = map.insert_many(vec!["a", "b", "c"]);
+    let expected: Vec<_> =
+        vec![5, 7, 8].into_iter().map(Handle::from_u64).collect();
+    assert_eq!(handles, expected);
+    assert_eq!(map.get(Handle::from_u64(6)), Some(&"old"));
+    assert_eq!(map.len(), 4);
+}
+
+#[test]
+fn narrow_handles_skip_occupied_handles() {
+    let mut map: RandMap<&str, StepRng, u32> = RandMap::from_rng(rigged());
+    map.insert_key_value(Handle::from_u32(5), "old");
+    assert_eq!(map.insert("new"), Handle::from_u32(6));
+    assert_eq!(map.get(Handle::from_u32(5)), Some(&"old"));
+}
+
+#[test]
+fn replace_key_value_returns_the_displaced_value() {
+    let mut map = RandMap::with_rng(rigged());
+    let handle = map.insert("first".to_string());
+    assert_eq!(handle, Handle::from_u64(5));
+    assert_eq!(
+        map.replace_key_value(handle, "second".to_string()),
+        Some("first".to_string())
+    );
+    assert_eq!(map.get(handle).map(String::as_str), Some("second"));
+    assert_eq!(map.len(), 1);
+    // A fresh key displaces nothing.
+    let other = Handle::from_u64(4711);
+    assert_eq!(map.replace_key_value(other, "third".to_string()), None);
+    assert_eq!(map.len(), 2);
+    // `insert()` does not reuse the handle `replace_key_value()` took.
+    map.replace_key_value(Handle::from_u64(6), "fourth".to_string());
+    assert_eq!(map.insert("fifth".to_string()), Handle::from_u64(7));
+    assert_eq!(map.get(handle).map(String::as_str), Some("second"));
+}
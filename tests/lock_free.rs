@@ -0,0 +1,84 @@
+#![cfg(feature = "lock_free")]
+
+use rand_map::{Handle, LockFreeRandMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+const READERS: usize = 4;
+const WRITERS: usize = 4;
+const ROUNDS: usize = 5000;
+
+#[test]
+fn readers_see_fixed_items_while_writers_churn() {
+    let map = Arc::new(LockFreeRandMap::new());
+    let fixed: Arc<Vec<Handle<String>>> =
+        Arc::new((0..64).map(|i| map.insert(i.to_string())).collect());
+    let done = Arc::new(AtomicBool::new(false));
+    let readers: Vec<_> = (0..READERS)
+        .map(|_| {
+            let map = Arc::clone(&map);
+            let fixed = Arc::clone(&fixed);
+            let done = Arc::clone(&done);
+            thread::spawn(move || {
+                let mut reads = 0;
+                while !done.load(Ordering::Relaxed) || reads < ROUNDS {
+                    let i = reads % fixed.len();
+                    assert_eq!(map.get(fixed[i]), Some(i.to_string()));
+                    reads += 1;
+                }
+            })
+        })
+        .collect();
+    let writers: Vec<_> = (0..WRITERS)
+        .map(|t| {
+            let map = Arc::clone(&map);
+            thread::spawn(move || {
+                let mut kept = Vec::new();
+                for round in 0..ROUNDS {
+                    let value = format!("{}-{}", t, round);
+                    let handle = map.insert(value.clone());
+                    assert_eq!(map.get(handle), Some(value));
+                    if round % 3 == 0 {
+                        kept.push(handle);
+                    } else {
+                        assert!(map.remove(handle));
+                        assert!(!map.contains(handle));
+                    }
+                }
+                kept
+            })
+        })
+        .collect();
+    let kept: Vec<_> = writers
+        .into_iter()
+        .flat_map(|w| w.join().unwrap())
+        .collect();
+    done.store(true, Ordering::Relaxed);
+    for reader in readers {
+        reader.join().unwrap();
+    }
+    assert_eq!(map.len(), fixed.len() + kept.len());
+    for handle in kept {
+        assert!(map.contains(handle));
+    }
+}
+
+#[test]
+fn replace_and_drop_values() {
+    let counter = Arc::new(());
+    let map = LockFreeRandMap::new();
+    let handles: Vec<_> =
+        (0..100).map(|_| map.insert(Arc::clone(&counter))).collect();
+    for handle in &handles[..50] {
+        assert!(map.insert_key_value(*handle, Arc::clone(&counter)));
+    }
+    for handle in &handles[50..] {
+        assert!(map.remove(*handle));
+    }
+    assert_eq!(map.len(), 50);
+    drop(map);
+    // Removed and replaced values are reclaimed lazily, but never more than
+    // once.
+    assert!(Arc::strong_count(&counter) <= 101);
+}
@@ -0,0 +1,79 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_map::{BTreeRandMap, Handle, RandMap};
+use std::collections::BTreeMap;
+use std::ops::Bound::{Excluded, Included, Unbounded};
+
+#[test]
+fn ranges_follow_a_btree_model() {
+    let mut rng = StdRng::seed_from_u64(4711);
+    let mut map = BTreeRandMap::new();
+    let mut model: BTreeMap<Handle<u32>, u32> = BTreeMap::new();
+    for step in 0..2_000 {
+        if rng.gen_range(0..3) == 0 && !model.is_empty() {
+            let index = rng.gen_range(0..model.len());
+            let handle = *model.keys().nth(index).unwrap();
+            assert_eq!(map.remove(handle), model.remove(&handle));
+        } else {
+            model.insert(map.insert(step), step);
+        }
+    }
+    let items: Vec<_> = map.iter().map(|(h, v)| (h, *v)).collect();
+    assert_eq!(
+        items,
+        model.iter().map(|(h, v)| (*h, *v)).collect::<Vec<_>>()
+    );
+    assert_eq!(map.first().map(|(h, _)| h), model.keys().next().copied());
+    assert_eq!(
+        map.last().map(|(h, _)| h),
+        model.keys().next_back().copied()
+    );
+    for _ in 0..100 {
+        let a = Handle::from_u64(rng.gen());
+        let b = Handle::from_u64(rng.gen());
+        let (low, high) = if a <= b { (a, b) } else { (b, a) };
+        let bounds = (Included(low), Excluded(high));
+        let got: Vec<_> = map.range(bounds).map(|(h, _)| h).collect();
+        let want: Vec<_> = model.range(bounds).map(|(h, _)| *h).collect();
+        assert_eq!(got, want);
+        let got: Vec<_> = map.range(low..).rev().map(|(h, _)| h).collect();
+        let want: Vec<_> =
+            model.range(low..).rev().map(|(h, _)| *h).collect();
+        assert_eq!(got, want);
+    }
+}
+
+#[test]
+fn partitions_cover_the_map() {
+    let mut map = BTreeRandMap::new();
+    for i in 0..1_000 {
+        map.insert(i);
+    }
+    let bounds: Vec<_> = (1..4).map(|i| Handle::from_u64(i << 62)).collect();
+    let mut total = 0;
+    total += map.range(..bounds[0]).count();
+    for pair in bounds.windows(2) {
+        total += map.range(pair[0]..pair[1]).count();
+    }
+    total += map.range((Included(bounds[2]), Unbounded)).count();
+    assert_eq!(total, 1_000);
+
+    for (_, value) in map.range_mut(bounds[0]..) {
+        *value = 0;
+    }
+    let mut high = map.clone().split_off(bounds[0]);
+    assert!(high.iter().all(|(_, &v)| v == 0));
+    assert_eq!(high.len() + map.range(..bounds[0]).count(), 1_000);
+    let (last, _) = high.pop_last().unwrap();
+    assert_eq!(map.last().map(|(h, _)| h), Some(last));
+}
+
+#[test]
+fn converts_from_and_into_rand_map() {
+    let mut rand_map = RandMap::new();
+    let handles: Vec<_> = (0..50).map(|i| rand_map.insert(i)).collect();
+    let map = BTreeRandMap::from(rand_map.clone());
+    for (i, handle) in handles.iter().enumerate() {
+        assert_eq!(map.get(*handle), Some(&i));
+    }
+    assert_eq!(RandMap::from(map), rand_map);
+}
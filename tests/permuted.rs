@@ -0,0 +1,111 @@
+use rand_map::{Handle, PermutedRandMap};
+use std::collections::HashSet;
+
+#[test]
+fn slots_are_reused_without_reviving_handles() {
+    let mut map = PermutedRandMap::new();
+    let mut removed = Vec::new();
+    for round in 0..100 {
+        let handles: Vec<_> =
+            (0..10).map(|i| map.insert(round * 10 + i)).collect();
+        for handle in handles {
+            assert!(map.remove(handle).is_some());
+            removed.push(handle);
+        }
+    }
+    assert!(map.is_empty());
+    let live = map.insert(4711);
+    assert_eq!(removed.iter().collect::<HashSet<_>>().len(), removed.len());
+    for handle in removed {
+        assert_eq!(map.get(handle), None);
+        assert_ne!(handle, live);
+    }
+    assert_eq!(map.iter().collect::<Vec<_>>(), vec![(live, &4711)]);
+    for (handle, value) in &map {
+        assert_eq!((handle, value), (live, &4711));
+    }
+}
+
+#[test]
+fn clearing_does_not_revive_handles() {
+    let mut map = PermutedRandMap::with_key(4711);
+    let before: Vec<_> = (0..10).map(|i| map.insert(i)).collect();
+    assert_eq!(map.remove(before[3]), Some(3));
+    map.clear();
+    assert!(map.is_empty());
+    let after: Vec<_> = (10..30).map(|i| map.insert(i)).collect();
+    assert_eq!(map.len(), 20);
+    for handle in &before {
+        assert_eq!(map.get(*handle), None);
+        assert!(!after.contains(handle));
+    }
+    for (i, handle) in after.into_iter().enumerate() {
+        assert_eq!(map.get(handle), Some(&(i + 10)));
+    }
+}
+
+#[test]
+fn removing_in_bulk_does_not_revive_handles() {
+    let mut map = PermutedRandMap::with_key(4711);
+    let handles: Vec<_> = (0..100).map(|i| map.insert(i)).collect();
+    for (_, value) in &mut map {
+        *value *= 2;
+    }
+    map.retain(|_, &mut value| value % 4 == 0);
+    assert_eq!(map.len(), 50);
+    for (i, &handle) in handles.iter().enumerate() {
+        let expected = Some(2 * i).filter(|_| i % 2 == 0);
+        assert_eq!(map.get(handle), expected.as_ref());
+    }
+
+    let mut drained: Vec<_> = map.drain().collect();
+    drained.sort_by_key(|&(_, value)| value);
+    let expected: Vec<_> = (0..50).map(|i| (handles[2 * i], 4 * i)).collect();
+    assert_eq!(drained, expected);
+    assert!(map.is_empty());
+    let fresh: Vec<_> = (0..100).map(|i| map.insert(i)).collect();
+    for handle in &handles {
+        assert_eq!(map.get(*handle), None);
+        assert!(!fresh.contains(handle));
+    }
+
+    // Pairs the iterator does not get to are removed, too.
+    assert_eq!(map.drain().take(10).count(), 10);
+    assert!(map.is_empty());
+    assert!(fresh.iter().all(|&handle| !map.contains(handle)));
+
+    let owned: Vec<_> = (0..10).map(|i| map.insert(i)).collect();
+    let mut items: Vec<_> = map.into_iter().collect();
+    items.sort_by_key(|&(_, value)| value);
+    assert_eq!(items, owned.into_iter().zip(0..10).collect::<Vec<_>>());
+}
+
+#[test]
+fn handles_depend_on_the_key() {
+    let mut a = PermutedRandMap::with_key(1);
+    let mut b = PermutedRandMap::with_key(1);
+    let mut c = PermutedRandMap::with_key(2);
+    let handles: Vec<_> = (0..1000).map(|i| a.insert(i)).collect();
+    for (i, &handle) in handles.iter().enumerate() {
+        assert_eq!(b.insert(i), handle);
+        assert_ne!(c.insert(i).as_u64(), handle.as_u64());
+    }
+    // Sequential slots do not give sequential handles.
+    let adjacent = handles
+        .windows(2)
+        .filter(|w| w[1].as_u64().wrapping_sub(w[0].as_u64()) < 1 << 32)
+        .count();
+    assert!(adjacent < 10);
+}
+
+#[test]
+fn guessed_handles_refer_to_nothing() {
+    let mut map = PermutedRandMap::new();
+    for i in 0..1000 {
+        map.insert(i);
+    }
+    let found = (0..100_000)
+        .filter(|&u| map.contains(Handle::from_u64(u)))
+        .count();
+    assert_eq!(found, 0);
+}
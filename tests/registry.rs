@@ -0,0 +1,40 @@
+use rand::{rngs::StdRng, SeedableRng};
+use rand_map::{Handle, RandRegistry};
+use std::any::TypeId;
+use std::collections::HashSet;
+
+#[test]
+fn handles_are_unique_across_types() {
+    let mut registry = RandRegistry::with_rng(StdRng::seed_from_u64(4711));
+    let mut raw = HashSet::new();
+    for i in 0..1_000u32 {
+        assert!(raw.insert(registry.insert(i).as_u64()));
+        assert!(raw.insert(registry.insert(i.to_string()).as_u64()));
+        assert!(raw.insert(registry.insert(vec![i]).as_u64()));
+    }
+    assert_eq!(registry.len(), 3_000);
+    assert_eq!(registry.iter::<u32>().count(), 1_000);
+    assert_eq!(registry.iter::<u64>().count(), 0);
+    for (handle, value) in registry.iter::<String>() {
+        assert_eq!(registry.get(handle), Some(value));
+    }
+}
+
+#[test]
+fn wrong_types_are_left_alone() {
+    let mut registry = RandRegistry::new();
+    let foo = registry.insert("foo");
+    let forged = Handle::<String>::from_u64(foo.as_u64());
+    assert!(!registry.contains(forged));
+    assert_eq!(registry.get_mut(forged), None);
+    assert_eq!(registry.remove(forged), None);
+    assert!(registry.contains(foo));
+
+    let (value, type_id) = registry.get_any(foo.as_u64()).unwrap();
+    assert_eq!(type_id, TypeId::of::<&str>());
+    assert_eq!(value.downcast_ref::<&str>(), Some(&"foo"));
+    let boxed = registry.remove_any(foo.as_u64()).unwrap();
+    assert_eq!(*boxed.downcast::<&str>().unwrap(), "foo");
+    assert!(registry.get_any(foo.as_u64()).is_none());
+    assert!(registry.is_empty());
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "serialize")]
+
+use rand_map::{Handle, RandMap, SecureHandle, SecureRandMap};
+
+fn sample() -> (RandMap<String>, Vec<Handle<String>>) {
+    let mut map = RandMap::new();
+    let handles = ["foo", "bar", "baz"]
+        .iter()
+        .map(|s| map.insert(s.to_string()))
+        .collect();
+    map.insert_key_value(Handle::from_u64(0), "zero".to_string());
+    map.insert_key_value(Handle::from_u64(u64::MAX), "max".to_string());
+    (map, handles)
+}
+
+fn check(map: &RandMap<String>, handles: &[Handle<String>]) {
+    assert_eq!(map.len(), 5);
+    for (handle, value) in handles.iter().zip(["foo", "bar", "baz"].iter()) {
+        assert_eq!(map.get(*handle).unwrap(), value);
+    }
+    assert_eq!(map.get(Handle::from_u64(0)).unwrap(), "zero");
+    assert_eq!(map.get(Handle::from_u64(u64::MAX)).unwrap(), "max");
+}
+
+#[test]
+fn handle_is_its_u64() {
+    let handle = Handle::<String>::from_u64(4711);
+    assert_eq!(serde_json::to_string(&handle).unwrap(), "4711");
+    let back: Handle<String> = serde_json::from_str("4711").unwrap();
+    assert_eq!(back, handle);
+    assert_eq!(
+        bincode::serialize(&handle).unwrap(),
+        bincode::serialize(&4711u64).unwrap()
+    );
+}
+
+#[test]
+fn json_object_keyed_by_handle_strings() {
+    let mut map = RandMap::new();
+    map.insert_key_value(Handle::from_u64(4711), 42);
+    assert_eq!(serde_json::to_string(&map).unwrap(), r#"{"4711":42}"#);
+    let back: RandMap<i32> = serde_json::from_str(r#"{"17":1}"#).unwrap();
+    assert_eq!(back.get(Handle::from_u64(17)), Some(&1));
+    assert!(serde_json::from_str::<RandMap<i32>>(r#"{"x":1}"#).is_err());
+}
+
+#[test]
+fn json_round_trip() {
+    let (map, handles) = sample();
+    let json = serde_json::to_string(&map).unwrap();
+    let back: RandMap<String> = serde_json::from_str(&json).unwrap();
+    check(&back, &handles);
+    assert!(back == map);
+}
+
+#[test]
+fn bincode_round_trip() {
+    let (map, handles) = sample();
+    let bytes = bincode::serialize(&map).unwrap();
+    let back: RandMap<String> = bincode::deserialize(&bytes).unwrap();
+    check(&back, &handles);
+    assert!(back == map);
+}
+
+#[test]
+fn message_pack_round_trip() {
+    let (map, handles) = sample();
+    let bytes = rmp_serde::to_vec(&map).unwrap();
+    let back: RandMap<String> = rmp_serde::from_slice(&bytes).unwrap();
+    check(&back, &handles);
+    assert!(back == map);
+}
+
+#[test]
+fn deserialized_map_inserts_fresh_handles() {
+    let (map, handles) = sample();
+    let json = serde_json::to_string(&map).unwrap();
+    let mut back: RandMap<String> = serde_json::from_str(&json).unwrap();
+    let new = back.insert("new".to_string());
+    assert!(!handles.contains(&new));
+    back.remove(new);
+    check(&back, &handles);
+}
+
+#[test]
+fn wide_handles_round_trip() {
+    let mut map: SecureRandMap<String> = SecureRandMap::default();
+    let foo = map.insert("foo".to_string());
+    let max = SecureHandle::from_u128(u128::MAX);
+    map.insert_key_value(max, "max".to_string());
+    let json = serde_json::to_string(&map).unwrap();
+    assert!(json.contains(&format!("\"{}\":\"max\"", u128::MAX)));
+    let back: SecureRandMap<String> = serde_json::from_str(&json).unwrap();
+    assert!(back == map);
+    let bytes = bincode::serialize(&map).unwrap();
+    let back: SecureRandMap<String> = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(back.get(foo).unwrap(), "foo");
+    assert_eq!(back.get(max).unwrap(), "max");
+}
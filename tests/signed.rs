@@ -0,0 +1,96 @@
+#![cfg(feature = "signed")]
+
+use rand_map::{Handle, SignedRandMap, VerifyError};
+
+fn split(signed: &str) -> (&str, &str) {
+    let mut parts = signed.splitn(2, '.');
+    (parts.next().unwrap(), parts.next().unwrap())
+}
+
+#[test]
+fn swapped_tags_are_rejected() {
+    let mut map = SignedRandMap::new(b"secret");
+    let foo = map.insert("foo");
+    let bar = map.insert("bar");
+    let (foo_handle, foo_tag) = split(&foo);
+    let (bar_handle, bar_tag) = split(&bar);
+    let swapped = format!("{}.{}", foo_handle, bar_tag);
+    assert_eq!(map.verify(&swapped), Err(VerifyError::BadTag));
+    let swapped = format!("{}.{}", bar_handle, foo_tag);
+    assert_eq!(map.verify_and_get(&swapped), Err(VerifyError::BadTag));
+    assert_eq!(map.verify_and_get(&bar), Ok(Some(&"bar")));
+}
+
+#[test]
+fn non_canonical_spellings_are_the_same_handle() {
+    let mut map = SignedRandMap::new(b"secret");
+    // A handle with digits that have other spellings.
+    let handle: Handle<&str> = "1ABC01XYZ".parse().unwrap();
+    map.as_rand_map_mut().insert_key_value(handle, "foo");
+    let foo = map.sign(handle);
+    let (proper, tag) = split(&foo);
+    let spellings = vec![
+        foo.to_lowercase(),
+        format!("{}-{}.{}", &proper[..3], &proper[3..], tag),
+        format!("{}.{}-{}", proper, &tag[..5], &tag[5..]),
+        foo.replace('0', "O").replace('1', "I"),
+        foo.replace('1', "l"),
+    ];
+    for spelling in &spellings {
+        assert_ne!(spelling, &foo);
+        assert_eq!(map.verify(spelling), Ok(handle), "{}", spelling);
+    }
+    // Leading zeros, as long as the digits fit.
+    let padded = format!(
+        "{:0>13}.{:0>26}",
+        proper.trim_start_matches('0'),
+        tag.trim_start_matches('0')
+    );
+    assert_eq!(map.verify(&padded), Ok(handle));
+    // Re-signing gives the canonical spelling.
+    assert_eq!(map.sign(handle), foo);
+}
+
+#[test]
+fn overlong_parts_are_malformed() {
+    let mut map = SignedRandMap::new(b"secret");
+    let foo = map.insert("foo");
+    let (proper, tag) = split(&foo);
+    for signed in &[
+        format!("{}.0{:0>26}", proper, tag),
+        format!("{}.{:0>27}", proper, tag),
+        format!("0{:0>13}.{}", proper, tag),
+        format!("{}.{}{}", proper, tag, tag),
+    ] {
+        assert_eq!(
+            map.verify_and_get(signed),
+            Err(VerifyError::Malformed),
+            "{}",
+            signed
+        );
+    }
+    assert_eq!(
+        map.verify(&format!("{}.", proper)),
+        Err(VerifyError::Malformed)
+    );
+    assert_eq!(
+        map.verify(&format!("{}.{}!", proper, tag)),
+        Err(VerifyError::Malformed)
+    );
+}
+
+#[test]
+fn keys_two_rotations_old_are_rejected_everywhere() {
+    let mut map = SignedRandMap::new(b"first");
+    let foo = map.insert("foo".to_string());
+    map.rotate_key(b"second");
+    *map.verify_and_get_mut(&foo).unwrap().unwrap() += "!";
+    map.rotate_key(b"third");
+    assert_eq!(map.verify_and_get(&foo), Err(VerifyError::BadTag));
+    assert_eq!(map.verify_and_get_mut(&foo), Err(VerifyError::BadTag));
+    assert_eq!(map.verify_and_remove(&foo), Err(VerifyError::BadTag));
+    // The item itself is untouched.
+    assert_eq!(map.len(), 1);
+    let value = map.as_rand_map().iter().next().unwrap().1;
+    assert_eq!(value, "foo!");
+}
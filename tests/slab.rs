@@ -0,0 +1,70 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_map::{Handle, RandMap, SlabRandMap};
+
+#[test]
+fn behaves_like_rand_map() {
+    let mut rng = StdRng::seed_from_u64(4711);
+    let mut slab = SlabRandMap::new();
+    let mut map = RandMap::new();
+    let mut handles: Vec<Handle<u32>> = Vec::new();
+    for step in 0..10_000 {
+        match rng.gen_range(0..4) {
+            0 | 1 => {
+                let handle = slab.insert(step);
+                map.insert_key_value(handle, step);
+                handles.push(handle);
+            }
+            2 if !handles.is_empty() => {
+                let handle =
+                    handles.swap_remove(rng.gen_range(0..handles.len()));
+                assert_eq!(slab.remove(handle), map.remove(handle));
+                assert_eq!(slab.remove(handle), None);
+            }
+            _ if !handles.is_empty() => {
+                let handle = handles[rng.gen_range(0..handles.len())];
+                *slab.get_mut(handle).unwrap() += 1;
+                *map.get_mut(handle).unwrap() += 1;
+            }
+            _ => (),
+        }
+        assert_eq!(slab.len(), map.len());
+    }
+    for (handle, value) in &slab {
+        assert_eq!(map.get(handle), Some(value));
+    }
+    for (i, handle) in slab.handles_slice().iter().enumerate() {
+        assert_eq!(slab.get(*handle), Some(&slab.values_slice()[i]));
+    }
+    let converted: RandMap<u32> = slab.clone().into();
+    assert!(converted == map);
+    assert!(SlabRandMap::from(map) == slab);
+}
+
+#[test]
+fn retain_and_drain() {
+    let mut slab = SlabRandMap::new();
+    let handles: Vec<_> = (0..100).map(|i| slab.insert(i)).collect();
+    slab.retain(|_, value| *value % 3 == 0);
+    assert_eq!(slab.len(), 34);
+    for (i, handle) in handles.iter().enumerate() {
+        assert_eq!(slab.get(*handle).is_some(), i % 3 == 0);
+    }
+    let mut drained: Vec<_> = slab.drain().map(|(_, value)| value).collect();
+    drained.sort_unstable();
+    assert_eq!(drained, (0..100).step_by(3).collect::<Vec<_>>());
+    assert!(slab.is_empty());
+    assert_eq!(slab.get(handles[0]), None);
+}
+
+#[test]
+fn replace_keeps_slab_position() {
+    let mut slab = SlabRandMap::new();
+    let foo = Handle::from_u64(1);
+    let bar = Handle::from_u64(2);
+    assert_eq!(slab.replace_key_value(foo, "foo"), None);
+    assert_eq!(slab.try_insert_key_value(bar, "bar"), Ok(()));
+    assert_eq!(slab.try_insert_key_value(bar, "BAR"), Err("BAR"));
+    assert_eq!(slab.replace_key_value(foo, "FOO"), Some("foo"));
+    assert_eq!(slab.values_slice(), &["FOO", "bar"]);
+    assert_eq!(slab.handles_slice(), &[foo, bar]);
+}
@@ -0,0 +1,175 @@
+use rand_map::{DefaultRng, RandMap, SnapshotError};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+fn temp_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join("rand_map_snapshot_tests");
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+// The temporary files left next to `path`.
+fn leftovers(path: &Path) -> Vec<PathBuf> {
+    let prefix = format!("{}.", path.file_name().unwrap().to_str().unwrap());
+    fs::read_dir(path.parent().unwrap())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|tmp| {
+            let name = tmp.file_name().unwrap().to_str().unwrap();
+            name.starts_with(&prefix) && name.ends_with(".tmp")
+        })
+        .collect()
+}
+
+fn sample() -> RandMap<String> {
+    let mut map = RandMap::new();
+    for i in 0..100 {
+        map.insert(format!("item {}", i));
+    }
+    map.insert(String::new());
+    map
+}
+
+#[test]
+fn round_trip_keeps_handles() {
+    let path = temp_path("round_trip");
+    let map = sample();
+    map.save_to(&path).unwrap();
+    let loaded: RandMap<String> = RandMap::load_from(&path).unwrap();
+    assert_eq!(loaded, map);
+    for (handle, value) in &map {
+        assert_eq!(loaded.get(handle), Some(value));
+    }
+    // Equal maps give equal files, and no temporary file is left behind.
+    let first = fs::read(&path).unwrap();
+    loaded.save_to(&path).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), first);
+    assert_eq!(leftovers(&path), Vec::<PathBuf>::new());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn concurrent_saves_do_not_mix() {
+    const THREADS: usize = 8;
+    let path = temp_path("concurrent");
+    let maps: Vec<RandMap<u64>> = (0..THREADS as u64)
+        .map(|i| {
+            let mut map = RandMap::new();
+            map.insert_many((0..1_000).map(|j| i * 1_000 + j));
+            map
+        })
+        .collect();
+    let barrier = Arc::new(Barrier::new(THREADS));
+    let threads: Vec<_> = maps
+        .iter()
+        .cloned()
+        .map(|map| {
+            let path = path.clone();
+            let barrier = Arc::clone(&barrier);
+            thread::spawn(move || {
+                barrier.wait();
+                for _ in 0..10 {
+                    map.save_to(&path).unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let loaded: RandMap<u64> = RandMap::load_from(&path).unwrap();
+    assert!(maps.contains(&loaded));
+    assert_eq!(leftovers(&path), Vec::<PathBuf>::new());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn all_widths() {
+    let path = temp_path("widths");
+    let mut narrow: RandMap<u8, DefaultRng, u32> = RandMap::default();
+    narrow.insert_many(0..=255);
+    narrow.save_to(&path).unwrap();
+    assert_eq!(RandMap::load_from(&path).unwrap(), narrow);
+    assert!(matches!(
+        RandMap::<u8>::load_from(&path),
+        Err(SnapshotError::KeyWidthMismatch {
+            expected: 8,
+            found: 4
+        })
+    ));
+    let mut wide: RandMap<bool, DefaultRng, u128> = RandMap::default();
+    wide.insert_many(vec![false, true]);
+    wide.save_to(&path).unwrap();
+    assert_eq!(RandMap::load_from(&path).unwrap(), wide);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn truncated_files_are_rejected() {
+    let path = temp_path("truncated");
+    let full = temp_path("truncated_full");
+    sample().save_to(&full).unwrap();
+    let bytes = fs::read(&full).unwrap();
+    for len in 0..bytes.len() {
+        fs::write(&path, &bytes[..len]).unwrap();
+        match RandMap::<String>::load_from(&path) {
+            Err(SnapshotError::Truncated) => (),
+            other => panic!("length {}: {:?}", len, other.map(|_| ())),
+        }
+    }
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&full).unwrap();
+}
+
+#[test]
+fn corrupt_files_are_rejected() {
+    let path = temp_path("corrupt");
+    sample().save_to(&path).unwrap();
+    let bytes = fs::read(&path).unwrap();
+    for i in 0..bytes.len() {
+        let mut corrupt = bytes.clone();
+        corrupt[i] ^= 0x10;
+        fs::write(&path, &corrupt).unwrap();
+        assert!(RandMap::<String>::load_from(&path).is_err(), "byte {}", i);
+    }
+    let mut corrupt = bytes.clone();
+    corrupt[0] = b'X';
+    fs::write(&path, &corrupt).unwrap();
+    assert!(matches!(
+        RandMap::<String>::load_from(&path),
+        Err(SnapshotError::BadMagic)
+    ));
+    let mut corrupt = bytes.clone();
+    corrupt[8] = 2;
+    fs::write(&path, &corrupt).unwrap();
+    assert!(matches!(
+        RandMap::<String>::load_from(&path),
+        Err(SnapshotError::UnsupportedVersion(2))
+    ));
+    let mut corrupt = bytes.clone();
+    let last = corrupt.len() - 10;
+    corrupt[last] ^= 1;
+    fs::write(&path, &corrupt).unwrap();
+    assert!(matches!(
+        RandMap::<String>::load_from(&path),
+        Err(SnapshotError::ChecksumMismatch)
+    ));
+    let mut longer = bytes;
+    longer.push(0);
+    fs::write(&path, &longer).unwrap();
+    assert!(matches!(
+        RandMap::<String>::load_from(&path),
+        Err(SnapshotError::Corrupt)
+    ));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn missing_file() {
+    let error =
+        RandMap::<String>::load_from(temp_path("missing")).unwrap_err();
+    assert!(matches!(error, SnapshotError::Io(_)));
+    assert!(std::error::Error::source(&error).is_some());
+}
@@ -0,0 +1,80 @@
+use rand::{rngs::StdRng, SeedableRng};
+use rand_map::{Handle, RandMap, TaggedRandMap, WrongMap};
+use std::time::{Duration, Instant};
+
+#[test]
+fn foreign_handles_are_rejected() {
+    let mut map1 = TaggedRandMap::with_rng(StdRng::seed_from_u64(1));
+    let mut map2 = TaggedRandMap::with_rng(StdRng::seed_from_u64(2));
+    assert_ne!(map1.tag(), map2.tag());
+    map1.set_checked(true);
+    map2.set_checked(true);
+    let own: Vec<_> = (0..100).map(|i| map1.insert(i)).collect();
+    let foreign: Vec<_> = (0..100).map(|i| map2.insert(i)).collect();
+    let wrong = WrongMap {
+        expected: map1.tag(),
+        found: map2.tag(),
+    };
+    for (&handle, value) in own.iter().zip(0..) {
+        assert_eq!(handle.as_u64() >> 48, u64::from(map1.tag()));
+        assert_eq!(map1.get(handle), Ok(Some(&value)));
+    }
+    for &handle in &foreign {
+        assert_eq!(map1.get(handle), Err(wrong));
+        assert_eq!(map1.get_mut(handle), Err(wrong));
+        assert_eq!(map1.contains(handle), Err(wrong));
+        assert_eq!(map1.remove(handle), Err(wrong));
+    }
+    assert_eq!(map1.len(), 100);
+    assert_eq!(map2.remove(foreign[0]), Ok(Some(0)));
+}
+
+#[test]
+fn unchecked_maps_only_miss() {
+    let mut map = TaggedRandMap::with_tag(0xbeef);
+    map.set_checked(false);
+    assert!(!map.checked());
+    let foo = map.insert("foo");
+    let forged = Handle::from_u64(foo.as_u64() ^ (1 << 63));
+    assert_eq!(map.get(forged), Ok(None));
+    assert_eq!(map.contains(foo), Ok(true));
+    map.set_checked(true);
+    assert_eq!(
+        map.get(forged).unwrap_err().to_string(),
+        "handle belongs to map 0x3eef, not 0xbeef"
+    );
+}
+
+#[test]
+fn checks_follow_the_build() {
+    let map = TaggedRandMap::<u8>::new();
+    assert_eq!(map.checked(), cfg!(debug_assertions));
+}
+
+#[test]
+fn large_maps_stay_fast() {
+    // All handles share their upper 16 bits. Unless the map spreads them,
+    // the items crowd the same few buckets, and this takes hundreds of times
+    // as long as with a plain map.
+    let start = Instant::now();
+    let mut plain = RandMap::with_rng(StdRng::seed_from_u64(4711));
+    let plain_handles: Vec<_> =
+        (0..200_000).map(|i| plain.insert(i)).collect();
+    for (i, &handle) in plain_handles.iter().enumerate() {
+        assert_eq!(plain.get(handle), Some(&i));
+    }
+    let plain_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut map = TaggedRandMap::with_rng(StdRng::seed_from_u64(4711));
+    let handles: Vec<_> = (0..200_000).map(|i| map.insert(i)).collect();
+    for (i, &handle) in handles.iter().enumerate() {
+        assert_eq!(map.get(handle), Ok(Some(&i)));
+    }
+    assert!(start.elapsed() < plain_time * 20 + Duration::from_millis(100));
+    let mut iterated: Vec<_> = map.iter().map(|(handle, _)| handle).collect();
+    iterated.sort();
+    let mut handles = handles;
+    handles.sort();
+    assert_eq!(iterated, handles);
+}
@@ -0,0 +1,43 @@
+use rand::rngs::mock::StepRng;
+use rand::{rngs::StdRng, SeedableRng};
+use rand_map::{DefaultRng, Handle, RandMap};
+use std::collections::HashSet;
+
+#[test]
+fn narrow_handles_survive_collisions() {
+    // At this size, a few collisions between random u32s are to be expected.
+    let mut map: RandMap<usize, DefaultRng, u32> = RandMap::default();
+    let handles = map.insert_many(0..200_000);
+    assert_eq!(map.len(), handles.len());
+    assert_eq!(handles.iter().collect::<HashSet<_>>().len(), handles.len());
+    for (i, handle) in handles.iter().enumerate() {
+        assert_eq!(map.get(*handle), Some(&i));
+    }
+}
+
+#[test]
+fn narrow_handles_retry_on_rigged_collision() {
+    let mut map: RandMap<&str, DefaultRng, u32> = RandMap::default();
+    for u in 1..=3 {
+        map.insert_key_value(Handle::from_u32(u), "taken");
+    }
+    let mut rng = StepRng::new(1, 1);
+    assert_eq!(map.insert_with_rng("fresh", &mut rng), Handle::from_u32(4));
+    assert_eq!(map.len(), 4);
+}
+
+#[test]
+fn wide_handles() {
+    let mut map: RandMap<u8, _, u128> =
+        RandMap::from_rng(StdRng::seed_from_u64(4711));
+    let handles = map.insert_many(0..=255);
+    // 128 random bits hardly ever fit in 64.
+    assert!(handles.iter().any(|h| h.as_u128() > u64::MAX as u128));
+    for (i, handle) in handles.iter().enumerate() {
+        assert_eq!(map.get(*handle), Some(&(i as u8)));
+    }
+    let max = Handle::from_u128(u128::MAX);
+    map.insert_key_value(max, 0);
+    assert_eq!(max.to_string().parse(), Ok(max));
+    assert_eq!(max.to_string().len(), 26);
+}